@@ -1,14 +1,30 @@
 #![allow(non_snake_case)]
 use crate::app::*;
-use anki_direct::notes::NoteAction;
+use crate::cache::{Cache, CacheConfig};
+use crate::cmds::write_to_errs_log;
+use crate::deinflect::{deinflect, is_inflection_of};
+use crate::filters::FilterConfig;
+use crate::frequency::FrequencyConfig;
+use crate::jmdict::JmdictConfig;
+use crate::journal::{finish_batch, record_batch, JournalBatch, JournalConfig, NoteSnapshot};
+use crate::known::KnownWordsConfig;
+use crate::pitch::{pitches_html, PitchConfig};
+use crate::prefetch::PrefetchConfig;
+use crate::sources::novels::NovelsConfig;
+use crate::sources::subtitles::SubtitlesConfig;
+use crate::sources::tatoeba::TatoebaConfig;
+use crate::tokenizer::TokenizerConfig;
+use crate::yomitan::YomitanConfig;
 use anki_direct::AnkiClient as AnkiDirectClient;
 use futures_util::future::join_all;
 //use futures_util::join;
 //use color_eyre::owo_colors::OwoColorize;
+use base64::Engine;
 use rayon::prelude::*;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::path::Path;
 use std::sync::{Arc, Mutex};
 //use tokio::runtime::Runtime;
 
@@ -16,21 +32,30 @@ use std::sync::{Arc, Mutex};
 struct Note {
     id: u128,
     fields: HashMap<String, String>,
-    audio: Option<Vec<Media>>,
-    picture: Option<Vec<Media>>,
 }
 
 #[derive(Serialize, Deserialize)]
-struct Media {
-    url: String,
-    filename: String,
-    skipHash: Option<String>,
-    fields: Vec<String>,
+struct UpdateNoteParams {
+    note: Note,
 }
 
 #[derive(Serialize, Deserialize)]
-struct UpdateNoteParams {
-    note: Note,
+struct AddNoteOptions {
+    allowDuplicate: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NewNote {
+    deckName: String,
+    modelName: String,
+    fields: HashMap<String, String>,
+    tags: Vec<String>,
+    options: AddNoteOptions,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AddNoteParams {
+    note: NewNote,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -38,8 +63,38 @@ struct FindNotesParams {
     query: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct NotesInfoParams {
+    notes: Vec<u128>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeleteNotesParams {
+    notes: Vec<u128>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GuiEditNoteParams {
+    note: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoreMediaParams {
+    filename: String,
+    /// Base64 encoded.
+    data: String,
+    deleteExisting: bool,
+}
+
 trait AnkiParams {}
 impl AnkiParams for UpdateNoteParams {}
+impl AnkiParams for AddNoteParams {}
+impl AnkiParams for FindNotesParams {}
+impl AnkiParams for NotesInfoParams {}
+impl AnkiParams for DeleteNotesParams {}
+impl AnkiParams for GuiEditNoteParams {}
+impl AnkiParams for StoreMediaParams {}
+impl<P: AnkiParams> AnkiParams for MultiParams<P> {}
 
 #[derive(Serialize, Deserialize)]
 struct Request<P: AnkiParams> {
@@ -48,53 +103,475 @@ struct Request<P: AnkiParams> {
     params: P,
 }
 
+/// Runs every action in one request.
+#[derive(Serialize, Deserialize)]
+struct MultiParams<P: AnkiParams> {
+    actions: Vec<Request<P>>,
+}
+
+/// AnkiConnect's reply, `result` is null if there's an `error`.
+#[derive(Deserialize)]
+struct ActionResult<R> {
+    result: Option<R>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct NoteField {
+    pub value: String,
+    pub order: u32,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct NoteInfo {
+    pub noteId: u128,
+    pub modelName: String,
+    pub tags: Vec<String>,
+    pub fields: HashMap<String, NoteField>,
+}
+
+type AnkiError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AnkiConnectConfig {
+    pub url: String,
+    /// Actions sent together in one `multi` request.
+    pub batch_size: usize,
+}
+
+impl Default for AnkiConnectConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:8765".to_string(),
+            batch_size: 50,
+        }
+    }
+}
+
+async fn anki_action<P: AnkiParams + Serialize, R: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+    action: &str,
+    params: P,
+) -> Result<Option<R>, AnkiError> {
+    let req = Request {
+        action: action.to_string(),
+        version: 6,
+        params,
+    };
+    let res: ActionResult<R> = client.post(url).json(&req).send().await?.json().await?;
+
+    match res.error {
+        Some(err) => Err(err.into()),
+        None => Ok(res.result),
+    }
+}
+
+/// Sends `reqs` in `multi` requests of `batch_size` actions, returning each action's result in
+/// order. If a whole request fails, all of its actions get its error.
+async fn anki_multi<P: AnkiParams + Serialize, R: DeserializeOwned>(
+    client: &reqwest::Client,
+    anki: &AnkiConnectConfig,
+    reqs: Vec<Request<P>>,
+) -> Vec<Result<Option<R>, String>> {
+    let mut results = Vec::with_capacity(reqs.len());
+    let mut reqs = reqs.into_iter().peekable();
+    while reqs.peek().is_some() {
+        let actions: Vec<Request<P>> = reqs.by_ref().take(anki.batch_size.max(1)).collect();
+        let len = actions.len();
+        let params = MultiParams { actions };
+        let res: Result<Option<Vec<ActionResult<R>>>, AnkiError> =
+            anki_action(client, &anki.url, "multi", params).await;
+
+        match res {
+            Ok(res) => {
+                let mut res: Vec<Result<Option<R>, String>> = res
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|res| match res.error {
+                        Some(err) => Err(err),
+                        None => Ok(res.result),
+                    })
+                    .collect();
+                res.resize_with(
+                    len,
+                    || Err("AnkiConnect didn't return a result".to_string()),
+                );
+                results.extend(res);
+            }
+            Err(err) => results.extend((0..len).map(|_| Err(err.to_string()))),
+        }
+    }
+    results
+}
+
+pub async fn find_note_ids(
+    client: &reqwest::Client,
+    url: &str,
+    query: &str,
+) -> Result<Vec<u128>, AnkiError> {
+    let params = FindNotesParams {
+        query: query.to_string(),
+    };
+    let ids = anki_action(client, url, "findNotes", params).await?;
+    Ok(ids.unwrap_or_default())
+}
+
+/// Note infos in the same order as `ids`, unknown ids give an empty `NoteInfo`.
+pub async fn notes_info(
+    client: &reqwest::Client,
+    url: &str,
+    ids: Vec<u128>,
+) -> Result<Vec<NoteInfo>, AnkiError> {
+    let params = NotesInfoParams { notes: ids };
+    let infos = anki_action(client, url, "notesInfo", params).await?;
+    Ok(infos.unwrap_or_default())
+}
+
+/// The fields of the notes that still exist, by id.
+async fn current_fields(
+    client: &reqwest::Client,
+    url: &str,
+    ids: Vec<u128>,
+) -> Result<HashMap<u128, HashMap<String, String>>, AnkiError> {
+    let infos = notes_info(client, url, ids.clone()).await?;
+    Ok(ids
+        .into_iter()
+        .zip(infos)
+        .filter(|(_, info)| info.noteId != 0)
+        .map(|(id, info)| (id, field_values(info)))
+        .collect())
+}
+
+fn field_values(info: NoteInfo) -> HashMap<String, String> {
+    info.fields
+        .into_iter()
+        .map(|(name, field)| (name, field.value))
+        .collect()
+}
+
+pub async fn delete_notes(
+    client: &reqwest::Client,
+    url: &str,
+    ids: Vec<u128>,
+) -> Result<(), AnkiError> {
+    let params = DeleteNotesParams { notes: ids };
+    anki_action::<_, serde_json::Value>(client, url, "deleteNotes", params).await?;
+    Ok(())
+}
+
+/// Uploads `bytes` to Anki's media folder, returning the name it was stored under.
+pub async fn store_media_file(
+    client: &reqwest::Client,
+    url: &str,
+    filename: &str,
+    bytes: &[u8],
+) -> Result<String, AnkiError> {
+    let params = StoreMediaParams {
+        filename: filename.to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        // Anki renames the file instead, if it has a different one with that name
+        deleteExisting: false,
+    };
+    let stored: Option<String> = anki_action(client, url, "storeMediaFile", params).await?;
+    Ok(stored.unwrap_or_else(|| filename.to_string()))
+}
+
+pub async fn gui_edit_note(client: &reqwest::Client, url: &str, id: u128) -> Result<(), AnkiError> {
+    let params = GuiEditNoteParams { note: id };
+    anki_action::<_, serde_json::Value>(client, url, "guiEditNote", params).await?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldPolicy {
+    #[default]
+    Overwrite,
+    /// Only written if the note's field is empty, so values added by hand are kept.
+    IfEmpty,
+}
+
+/// Either just the Anki field name, or `{"field": "...", "policy": "if_empty"}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum FieldMapping {
+    Name(String),
+    Detailed {
+        field: String,
+        #[serde(default)]
+        policy: FieldPolicy,
+    },
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        FieldMapping::Name(String::new())
+    }
+}
+
+impl FieldMapping {
+    pub fn name(&self) -> &str {
+        match self {
+            FieldMapping::Name(field) | FieldMapping::Detailed { field, .. } => field,
+        }
+    }
+
+    pub fn policy(&self) -> FieldPolicy {
+        match self {
+            FieldMapping::Name(_) => FieldPolicy::Overwrite,
+            FieldMapping::Detailed { policy, .. } => *policy,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct UserNoteFields {
     pub expression: String,
     pub sentence: String,
     pub sentence_audio: String,
     pub image: String,
+    /// Optional fields, left untouched when empty.
+    #[serde(default)]
+    pub reading: FieldMapping,
+    #[serde(default)]
+    pub definition: FieldMapping,
+    #[serde(default)]
+    pub translation: FieldMapping,
+    #[serde(default)]
+    pub furigana: FieldMapping,
+    #[serde(default)]
+    pub media_title: FieldMapping,
+    #[serde(default)]
+    pub source_episode: FieldMapping,
+    #[serde(default)]
+    pub pitch: FieldMapping,
+    #[serde(default)]
+    pub frequency: FieldMapping,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct ReqResult {
-    result: Option<Vec<u128>>,
-    error: Option<String>,
+impl UserNoteFields {
+    /// The mapped field names, in the order they're listed in the config.
+    fn names(&self) -> Vec<&str> {
+        let mut names = vec![
+            self.expression.as_str(),
+            &self.sentence,
+            &self.sentence_audio,
+            &self.image,
+        ];
+        let optional = [
+            &self.reading,
+            &self.definition,
+            &self.translation,
+            &self.furigana,
+            &self.media_title,
+            &self.source_episode,
+            &self.pitch,
+            &self.frequency,
+        ];
+        names.extend(optional.map(|mapping| mapping.name()));
+        names
+    }
 }
 
 #[derive(Debug)]
 struct AnkiSentence {
     sentence_obj: Sentence,
-    filename: Option<String>,
-    local_audio_url: Option<String>,
+    /// Names the audio & image are stored under in Anki's media folder.
+    audio: Option<String>,
+    picture: Option<String>,
+    /// Downloaded, but not stored in Anki yet.
+    audio_bytes: Option<Vec<u8>>,
+    picture_bytes: Option<Vec<u8>>,
+    /// The note's fields before the update.
+    current_fields: Option<HashMap<String, String>>,
+    media_error: Option<String>,
 }
 
 impl AnkiSentence {
-    fn into_anki_sentence(sentence: Sentence, config: &ConfigJson) -> Self {
-        let (filename, local_audio_url) = if let Some(audio_url) = &sentence.audio_url {
-            let filename = url_into_file_name(audio_url);
-            let local_audio_url = sentence.audio_data.as_ref().map(|audio_data| {
-                write_audio_bytes_file(&config.media_path, &filename, audio_data).unwrap()
-            });
-
-            (Some(filename), local_audio_url)
-        } else {
-            (None, None)
-        };
-
+    fn into_anki_sentence(sentence: Sentence) -> Self {
         Self {
             sentence_obj: sentence,
-            filename,
-            local_audio_url,
+            audio: None,
+            picture: None,
+            audio_bytes: None,
+            picture_bytes: None,
+            current_fields: None,
+            media_error: None,
+        }
+    }
+}
+
+/// What happened to one sentence of an update.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NoteOutcome {
+    Updated(u128),
+    /// A note was added, the word didn't have one yet.
+    Created(u128),
+    NoNote,
+    /// More than one note has the word as its expression.
+    Ambiguous(Vec<u128>),
+    /// A field from the config that isn't on the note.
+    FieldMissing(String),
+    /// The note was updated, but without its audio or image.
+    MediaFailed(u128, String),
+    Failed(String),
+}
+
+impl NoteOutcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, NoteOutcome::Updated(_) | NoteOutcome::Created(_))
+    }
+
+    pub fn note_id(&self) -> Option<u128> {
+        match self {
+            NoteOutcome::Updated(id)
+            | NoteOutcome::Created(id)
+            | NoteOutcome::MediaFailed(id, _) => Some(*id),
+            _ => None,
         }
     }
 }
 
+impl std::fmt::Display for NoteOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NoteOutcome::Updated(id) => write!(f, "Updated note {}", id),
+            NoteOutcome::Created(id) => write!(f, "Added note {}", id),
+            NoteOutcome::NoNote => write!(f, "No note found"),
+            NoteOutcome::Ambiguous(ids) => {
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                write!(f, "Notes {} all match, pick one with <I>", ids.join(", "))
+            }
+            NoteOutcome::FieldMissing(field) => write!(
+                f,
+                "Incorrect Field: `{}`; fields in config have to match the Anki Note",
+                field
+            ),
+            NoteOutcome::MediaFailed(id, err) => {
+                write!(f, "Updated note {} without media: {}", id, err)
+            }
+            NoteOutcome::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NoteResult {
+    pub sentence: Sentence,
+    pub outcome: NoteOutcome,
+}
+
+impl NoteResult {
+    fn new(sentence: Sentence, outcome: NoteOutcome) -> Self {
+        Self { sentence, outcome }
+    }
+}
+
+/// One result per sentence, in the order they were given.
 pub struct UpdateNotesRes {
-    pub dict_words_vec: Vec<String>,
-    pub err_vec: Vec<String>,
-    pub success_len: usize,
-    pub total_len: usize,
+    pub results: Vec<NoteResult>,
+}
+
+impl UpdateNotesRes {
+    pub fn success_len(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| r.outcome.is_success())
+            .count()
+    }
+
+    pub fn created_len(&self) -> usize {
+        let created = |r: &&NoteResult| matches!(r.outcome, NoteOutcome::Created(_));
+        self.results.iter().filter(created).count()
+    }
+}
+
+/// What an update does with one sentence's note.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NoteAction {
+    Update(u128),
+    /// The word doesn't have a note yet.
+    Add,
+    /// Nothing is written, the outcome says why.
+    Skip(NoteOutcome),
+}
+
+impl std::fmt::Display for NoteAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NoteAction::Update(id) => write!(f, "Update note {}", id),
+            NoteAction::Add => write!(f, "Add note"),
+            NoteAction::Skip(outcome) => write!(f, "Skip: {}", outcome),
+        }
+    }
+}
+
+/// A field the update writes, with the note's value before it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    /// `None` if the note doesn't have it yet.
+    pub current: Option<String>,
+    pub new: String,
+}
+
+impl FieldChange {
+    pub fn is_changed(&self) -> bool {
+        self.current.as_deref() != Some(self.new.as_str())
+    }
+}
+
+/// One sentence of an update, worked out before anything is written.
+#[derive(Debug)]
+pub struct PlannedNote {
+    sentence: AnkiSentence,
+    pub action: NoteAction,
+    /// In the order the fields are listed in the config.
+    pub changes: Vec<FieldChange>,
+    pub approved: bool,
+}
+
+impl PlannedNote {
+    pub fn sentence(&self) -> &Sentence {
+        &self.sentence.sentence_obj
+    }
+
+    pub fn media_error(&self) -> Option<&str> {
+        self.sentence.media_error.as_deref()
+    }
+}
+
+/// The notes an update would write to, in the order of the sentences.
+pub struct UpdatePlan {
+    pub notes: Vec<PlannedNote>,
+    /// The config the plan was made with, it's committed with the same one.
+    config: ConfigJson,
+}
+
+impl UpdatePlan {
+    /// Takes the sentences of the notes that weren't approved out of the plan.
+    pub fn take_unapproved(&mut self) -> Vec<Sentence> {
+        let (approved, unapproved): (Vec<PlannedNote>, Vec<PlannedNote>) =
+            std::mem::take(&mut self.notes)
+                .into_iter()
+                .partition(|note| note.approved);
+        self.notes = approved;
+        unapproved
+            .into_iter()
+            .map(|note| note.sentence.sentence_obj)
+            .collect()
+    }
+
+    pub fn into_sentences(self) -> Vec<Sentence> {
+        self.notes
+            .into_iter()
+            .map(|note| note.sentence.sentence_obj)
+            .collect()
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
@@ -102,110 +579,435 @@ pub struct ConfigOptions {
     pub del_words: bool,
     pub tts: bool,
     pub auto_load_new_notes: bool,
+    /// Shows what an update changes & waits for it to be approved.
+    #[serde(default)]
+    pub preview_updates: bool,
+}
+
+/// Where notes are added for words that don't have one yet.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct NewNoteConfig {
+    pub enabled: bool,
+    pub deck: String,
+    /// The note type, its fields have to match `fields`.
+    pub model: String,
+    pub tags: Vec<String>,
+}
+
+impl Default for NewNoteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            deck: "Default".to_string(),
+            model: "Basic".to_string(),
+            tags: vec!["anki_helper".to_string()],
+        }
+    }
 }
 
 // other
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ConfigJson {
     pub fields: UserNoteFields,
-    pub media_path: String,
     pub priority: Vec<String>,
     pub options: ConfigOptions,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub tatoeba: TatoebaConfig,
+    #[serde(default)]
+    pub subtitles: SubtitlesConfig,
+    #[serde(default)]
+    pub novels: NovelsConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub prefetch: PrefetchConfig,
+    #[serde(default)]
+    pub filters: FilterConfig,
+    #[serde(default)]
+    pub known_words: KnownWordsConfig,
+    #[serde(default)]
+    pub jmdict: JmdictConfig,
+    #[serde(default)]
+    pub yomitan: YomitanConfig,
+    #[serde(default)]
+    pub frequency: FrequencyConfig,
+    #[serde(default)]
+    pub pitch: PitchConfig,
+    #[serde(default)]
+    pub tokenizer: TokenizerConfig,
+    #[serde(default)]
+    pub new_notes: NewNoteConfig,
+    #[serde(default)]
+    pub anki_connect: AnkiConnectConfig,
+    #[serde(default)]
+    pub journal: JournalConfig,
 }
 
-pub async fn update_anki_cards(
+/// Finds each sentence's note & works out what the update writes to it, without writing anything.
+/// The audio & images are downloaded, but only stored in Anki once the plan is committed.
+pub async fn plan_note_updates(
     sentence_objs_vec: Vec<Sentence>,
     config: &ConfigJson,
-) -> Result<UpdateNotesRes, Box<dyn std::error::Error>> {
-    let client = AnkiDirectClient::default();
-    let client_clone = client.client.clone();
-    let sent_objs_vec_len = sentence_objs_vec.len();
-
-    let mut err_vec: Vec<String> = Vec::new();
-    let mut failed_words: Vec<&str> = Vec::new();
-    //let mut note_ids_and_sentences: Vec<(Option<u128>, AnkiSentence)> = Vec::new();
-    
-        let tasks: Vec<_> = sentence_objs_vec
-            .into_iter()
-            .map(|sent| {
-                let config = config.clone();
-                let client = client.clone();
-                tokio::task::spawn(async move {
-                    if let Some(id) = sent.parent_expression.note_id {
-                        let anki_sentence = AnkiSentence::into_anki_sentence(sent.clone(), &config);
-                        return (Some(id), anki_sentence);
-                    }
+) -> UpdatePlan {
+    let client = reqwest::Client::new();
+    let url = config.anki_connect.url.as_str();
+
+    // the id given with <I>, or the expression's, the rest are looked up together
+    let known: Vec<Option<u128>> = sentence_objs_vec
+        .iter()
+        .map(|sent| sent.note_id.or(sent.parent_expression.note_id))
+        .collect();
+    let words: Vec<String> = sentence_objs_vec
+        .iter()
+        .zip(&known)
+        .filter(|(_, id)| id.is_none())
+        .map(|(sent, _)| sent.parent_expression.dict_word.clone())
+        .collect();
+    let mut found = find_notes(&client, config, &words).await.into_iter();
+    let mut pending: Vec<(Result<u128, NoteOutcome>, AnkiSentence)> = known
+        .into_iter()
+        .zip(sentence_objs_vec)
+        .map(|(id, sent)| {
+            let mut anki_s = AnkiSentence::into_anki_sentence(sent);
+            let id = match id {
+                Some(id) => Ok(id),
+                // the search already read the note's fields
+                None => found
+                    .next()
+                    .unwrap_or(Err(NoteOutcome::NoNote))
+                    .map(|info| {
+                        let id = info.noteId;
+                        anki_s.current_fields = Some(field_values(info));
+                        id
+                    }),
+            };
+            (id, anki_s)
+        })
+        .collect();
 
-                    let exp = &sent.parent_expression;
-                    let id = match check_note_exists(&client, &exp.dict_word).await {
-                        Ok(id) => Some(id),
-                        Err(_e) => {
-                            // Handle error appropriately
-                            None
+    // the known notes' current fields, to check the mapped ones exist & for `if_empty` ones
+    let unread = |(id, anki_s): &(Result<u128, NoteOutcome>, AnkiSentence)| {
+        id.is_ok() && anki_s.current_fields.is_none()
+    };
+    let ids: Vec<u128> = pending
+        .iter()
+        .filter(|note| unread(note))
+        .filter_map(|(id, _)| id.as_ref().ok().copied())
+        .collect();
+    if !ids.is_empty() {
+        match current_fields(&client, url, ids).await {
+            Ok(current) => {
+                for (id, anki_s) in pending.iter_mut().filter(|note| unread(note)) {
+                    if let Ok(note_id) = id {
+                        match current.get(note_id) {
+                            Some(fields) => anki_s.current_fields = Some(fields.clone()),
+                            // deleted since it was found
+                            None => *id = Err(NoteOutcome::NoNote),
                         }
-                    };
+                    }
+                }
+            }
+            Err(err) => {
+                for (id, _) in pending.iter_mut().filter(|note| unread(note)) {
+                    *id = Err(NoteOutcome::Failed(format!(
+                        "Err Reading Note Fields: {}",
+                        err
+                    )));
+                }
+            }
+        }
+    }
 
-                    let anki_sentence = AnkiSentence::into_anki_sentence(sent.clone(), &config);
-                    (id, anki_sentence)
-                })
-            })
-            .collect();
+    let mut planned: Vec<(NoteAction, AnkiSentence)> = pending
+        .into_iter()
+        .map(|(id, mut anki_s)| {
+            let action = match id {
+                Ok(id) => NoteAction::Update(id),
+                Err(NoteOutcome::NoNote) if config.new_notes.enabled => {
+                    // a new note has nothing to keep, so `if_empty` fields are filled too
+                    anki_s.current_fields = Some(HashMap::new());
+                    NoteAction::Add
+                }
+                Err(outcome) => NoteAction::Skip(outcome),
+            };
+            (action, anki_s)
+        })
+        .collect();
+
+    let cache = Cache::new(&config.cache);
+    let downloads = planned
+        .iter_mut()
+        .filter(|(action, _)| !matches!(action, NoteAction::Skip(_)))
+        .map(|(_, anki_s)| {
+            let cache = &cache;
+            async move {
+                // the note is still updated, just without the media that failed
+                if let Err(err) = fetch_sentence_media(cache, anki_s).await {
+                    anki_s.media_error = Some(err);
+                }
+            }
+        });
+    join_all(downloads).await;
+
+    let notes = planned
+        .into_iter()
+        .map(|(action, anki_s)| {
+            let action = match action {
+                NoteAction::Update(id) => {
+                    let req = into_update_note_req(id, &config.fields, &anki_s);
+                    match missing_field(&req.params.note, &anki_s) {
+                        Some(field) => NoteAction::Skip(NoteOutcome::FieldMissing(field)),
+                        None => NoteAction::Update(id),
+                    }
+                }
+                action => action,
+            };
+            PlannedNote {
+                changes: field_changes(config, &action, &anki_s),
+                sentence: anki_s,
+                action,
+                approved: true,
+            }
+        })
+        .collect();
 
-        // Await all the tasks to complete
-        let results = join_all(tasks).await;
+    UpdatePlan {
+        notes,
+        config: config.clone(),
+    }
+}
 
-    let note_ids_and_sentences: Vec<(Option<u128>, AnkiSentence)> =
-        results.into_iter().map(|res| res.unwrap()).collect();
+/// Stores the media & writes the approved notes of the plan.
+pub async fn commit_note_updates(plan: UpdatePlan) -> UpdateNotesRes {
+    let client = reqwest::Client::new();
+    let config = &plan.config;
+    let anki = &config.anki_connect;
+    let url = anki.url.as_str();
+    let mut notes: Vec<PlannedNote> = plan.notes.into_iter().filter(|n| n.approved).collect();
+
+    let uploads = notes
+        .iter_mut()
+        .filter(|note| !matches!(note.action, NoteAction::Skip(_)))
+        .map(|note| {
+            let client = &client;
+            async move {
+                if let Err(err) = store_sentence_media(client, url, &mut note.sentence).await {
+                    note.sentence.media_error.get_or_insert(err);
+                }
+            }
+        });
+    join_all(uploads).await;
+
+    let mut results: Vec<(usize, NoteResult)> = Vec::new();
+    let mut updates: Vec<(usize, u128, AnkiSentence)> = Vec::new();
+    let mut additions: Vec<(usize, AnkiSentence)> = Vec::new();
+    for (i, note) in notes.into_iter().enumerate() {
+        match note.action {
+            NoteAction::Update(id) => updates.push((i, id, note.sentence)),
+            NoteAction::Add => additions.push((i, note.sentence)),
+            NoteAction::Skip(outcome) => {
+                results.push((i, NoteResult::new(note.sentence.sentence_obj, outcome)))
+            }
+        }
+    }
 
-    if note_ids_and_sentences.iter().all(|(id, _)| id.is_none()) {
-        return Err("Err: 0 IDs found. Check `err.log.txt` for errors.".into());
+    // the plan's fields can be minutes old after a preview, so the journal & `if_empty` fields
+    // go by the ones the notes have now
+    if !updates.is_empty() {
+        let ids = updates.iter().map(|(_, id, _)| *id).collect();
+        let current = current_fields(&client, url, ids).await;
+        let mut refreshed = Vec::with_capacity(updates.len());
+        for (i, id, mut anki_s) in updates {
+            let outcome = match &current {
+                Ok(current) => match current.get(&id) {
+                    Some(fields) => {
+                        anki_s.current_fields = Some(fields.clone());
+                        refreshed.push((i, id, anki_s));
+                        continue;
+                    }
+                    None => NoteOutcome::NoNote,
+                },
+                Err(err) => NoteOutcome::Failed(format!("Err Reading Note Fields: {}", err)),
+            };
+            results.push((i, NoteResult::new(anki_s.sentence_obj, outcome)));
+        }
+        updates = refreshed;
     }
-    let nias_len = note_ids_and_sentences.len();
 
-    let dict_words_vec: Vec<String> = note_ids_and_sentences
-        .par_iter()
-        .filter_map(|s| {
-            let dw = s.1.sentence_obj.parent_expression.dict_word.clone();
-            if failed_words.contains(&dw.as_str()) {
-                None
-            } else {
-                Some(dw)
+    let reqs: Vec<Request<UpdateNoteParams>> = updates
+        .iter()
+        .map(|(_, id, anki_s)| into_update_note_req(*id, &config.fields, anki_s))
+        .collect();
+    // the fields are journaled before they're written, so the update can always be undone
+    let mut journal_err = None;
+    let mut batch_id = None;
+    if config.journal.enabled && !(reqs.is_empty() && additions.is_empty()) {
+        let notes = reqs
+            .iter()
+            .zip(&updates)
+            .map(|(req, (_, _, anki_s))| snapshot_note(&req.params.note, anki_s))
+            .collect();
+        let batch = JournalBatch::new(notes);
+        let id = batch.id;
+        match record_batch(&config.journal, batch) {
+            Ok(warning) => {
+                if let Some(warning) = warning {
+                    let _ = write_to_errs_log(&vec![warning]);
+                }
+                batch_id = Some(id);
             }
+            Err(err) => journal_err = Some(err),
+        }
+    }
+    let update_results = match journal_err {
+        None => post_note_updates(reqs, &client, anki).await,
+        Some(err) => {
+            let err = format!("Err Writing Undo Journal: {}", err);
+            reqs.iter().map(|_| Err(err.clone())).collect()
+        }
+    };
+    for ((i, id, anki_s), result) in updates.into_iter().zip(update_results) {
+        let outcome = match (result, anki_s.media_error) {
+            (Ok(()), None) => NoteOutcome::Updated(id),
+            (Ok(()), Some(err)) => NoteOutcome::MediaFailed(id, err),
+            (Err(err), _) => NoteOutcome::Failed(err),
+        };
+        results.push((i, NoteResult::new(anki_s.sentence_obj, outcome)));
+    }
+
+    let add_reqs = additions
+        .iter()
+        .map(|(_, anki_s)| into_add_note_req(config, anki_s))
+        .collect();
+    let add_results = post_new_notes(add_reqs, &client, anki).await;
+    let created: Vec<u128> = add_results
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok().copied())
+        .collect();
+    if let Some(batch_id) = batch_id.filter(|_| !add_results.is_empty()) {
+        // the added notes are only known now, they're deleted when the batch is undone
+        if let Err(err) = finish_batch(&config.journal, batch_id, created.clone()) {
+            let _ = write_to_errs_log(&vec![format!(
+                "Err Writing Undo Journal, the added notes {:?} won't be deleted by an undo: {}",
+                created, err
+            )]);
+        }
+    }
+    for ((i, anki_s), (_, result)) in additions.into_iter().zip(add_results) {
+        let outcome = match (result, anki_s.media_error) {
+            (Ok(id), None) => NoteOutcome::Created(id),
+            (Ok(id), Some(err)) => NoteOutcome::MediaFailed(id, err),
+            (Err(err), _) => NoteOutcome::Failed(err),
+        };
+        results.push((i, NoteResult::new(anki_s.sentence_obj, outcome)));
+    }
+
+    results.sort_by_key(|(i, _)| *i);
+    UpdateNotesRes {
+        results: results.into_iter().map(|(_, result)| result).collect(),
+    }
+}
+
+/// Plans & commits the update in one go.
+pub async fn update_anki_cards(
+    sentence_objs_vec: Vec<Sentence>,
+    config: &ConfigJson,
+) -> UpdateNotesRes {
+    let plan = plan_note_updates(sentence_objs_vec, config).await;
+    commit_note_updates(plan).await
+}
+
+/// The values the update's fields have on the note before it's written.
+fn snapshot_note(note: &Note, sentence: &AnkiSentence) -> NoteSnapshot {
+    let current = sentence.current_fields.as_ref();
+    let fields = note
+        .fields
+        .keys()
+        .map(|field| {
+            let value = current.and_then(|fields| fields.get(field)).cloned();
+            (field.clone(), value.unwrap_or_default())
         })
         .collect();
+    NoteSnapshot {
+        id: note.id,
+        dict_word: sentence.sentence_obj.parent_expression.dict_word.clone(),
+        fields,
+    }
+}
 
-    let requests_vec: Vec<Request<UpdateNoteParams>> = note_ids_and_sentences
-        .into_par_iter()
-        .filter_map(|(id, anki_s)| {
-            if let Some(note_id) = id {
-                let req: Request<UpdateNoteParams> = match &anki_s.filename.clone() {
-                    Some(filename) => {
-                        into_update_note_req(note_id, &config.fields, anki_s, filename.to_string())
-                    }
-                    None => into_update_only_sentence_req(note_id, &config.fields, &anki_s),
-                };
-                return Some(req);
-            }
-            None
+/// Writes the snapshots' fields back & deletes the notes the update added, returning a result
+/// for each of the batch's `notes` followed by each of its `created` ones.
+pub async fn undo_batch(batch: &JournalBatch, config: &ConfigJson) -> Vec<Result<(), String>> {
+    let client = reqwest::Client::new();
+    let anki = &config.anki_connect;
+    let reqs = batch
+        .notes
+        .iter()
+        .map(|snapshot| Request {
+            action: "updateNoteFields".to_string(),
+            version: 6,
+            params: UpdateNoteParams {
+                note: Note {
+                    id: snapshot.id,
+                    fields: snapshot.fields.clone(),
+                },
+            },
         })
         .collect();
+    let mut results = post_note_updates(reqs, &client, anki).await;
 
-    match post_note_updates(requests_vec, &client_clone).await {
-        Ok(_) => {
-            let result = UpdateNotesRes {
-                dict_words_vec,
-                success_len: nias_len,
-                err_vec,
-                total_len: sent_objs_vec_len,
-            };
+    if !batch.created.is_empty() {
+        let deleted = delete_notes(&client, &anki.url, batch.created.clone())
+            .await
+            .map_err(|err| err.to_string());
+        results.extend(batch.created.iter().map(|_| deleted.clone()));
+    }
+    results
+}
 
-            Ok(result)
+/// The fields `action` writes, next to the note's current values.
+fn field_changes(
+    config: &ConfigJson,
+    action: &NoteAction,
+    sentence: &AnkiSentence,
+) -> Vec<FieldChange> {
+    let fields = match action {
+        NoteAction::Update(id) => {
+            into_update_note_req(*id, &config.fields, sentence)
+                .params
+                .note
+                .fields
         }
-        Err(err) => Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Network Err: {}", err),
-        ))),
-    }
+        NoteAction::Add => into_add_note_req(config, sentence).1.params.note.fields,
+        NoteAction::Skip(_) => return Vec::new(),
+    };
+    let current = sentence.current_fields.as_ref();
+    let mut changes: Vec<FieldChange> = fields
+        .into_iter()
+        .map(|(field, new)| FieldChange {
+            current: current.and_then(|fields| fields.get(&field).cloned()),
+            field,
+            new,
+        })
+        .collect();
+    let order = config.fields.names();
+    changes.sort_by_key(|change| order.iter().position(|name| *name == change.field));
+    changes
+}
+
+/// The first field the update writes that the note doesn't have.
+fn missing_field(note: &Note, sentence: &AnkiSentence) -> Option<String> {
+    let current = sentence.current_fields.as_ref()?;
+    let mut missing: Vec<&String> = note
+        .fields
+        .keys()
+        .filter(|name| !current.contains_key(*name))
+        .collect();
+    missing.sort();
+    missing.first().map(|name| name.to_string())
 }
 
 impl AppState {
@@ -217,7 +1019,10 @@ impl AppState {
             .par_iter_mut()
             .filter_map(|exp| {
                 let wrd = &exp.dict_word.clone();
-                if res.dict_words_vec.contains(wrd) {
+                let updated = |r: &NoteResult| {
+                    r.outcome.is_success() && r.sentence.parent_expression.dict_word == *wrd
+                };
+                if res.results.iter().any(updated) {
                     Some(wrd.clone())
                 } else {
                     None
@@ -236,7 +1041,8 @@ impl AppState {
         }
         if let Some(i) = self.selected_expression {
             if let Some(id) = self.expressions[i].note_id {
-                match NoteAction::gui_edit_note(&self.client, id).await {
+                let url = &self.config.anki_connect.url;
+                match gui_edit_note(&self.client.client, url, id).await {
                     Ok(res) => res,
                     Err(e) => self.update_error_msg("Err Opening Note", e.to_string()),
                 }
@@ -250,8 +1056,9 @@ pub async fn return_new_anki_words(
     config: &ConfigJson,
     query: &str,
 ) -> Result<Vec<Expression>, Box<dyn std::error::Error + Send + Sync>> {
-    let ids = NoteAction::find_note_ids(client, query).await?;
-    let infos = NoteAction::get_notes_infos(client, ids.clone()).await?;
+    let url = &config.anki_connect.url;
+    let ids = find_note_ids(&client.client, url, query).await?;
+    let infos = notes_info(&client.client, url, ids.clone()).await?;
 
     // Shared error state
     let error: Arc<Mutex<Option<Box<dyn std::error::Error + Send + Sync>>>> =
@@ -311,47 +1118,37 @@ fn url_into_file_name(url: &str) -> String {
         .to_string()
 }
 
-async fn direct_find_note_from_word(
-    client: &AnkiDirectClient,
-    word: &str,
-) -> Result<u128, Box<dyn std::error::Error>> {
-    let id_vec = NoteAction::find_note_ids(client, word).await?;
-
-    match id_vec.last() {
-        Some(id) => Ok(*id),
-        None => Err(format!("No notes found for: {}", &word).into()),
-    }
-}
-
+/// Posts the `updateNoteFields` requests in batches, returning each one's error.
 async fn post_note_updates(
     reqs: Vec<Request<UpdateNoteParams>>,
     client: &reqwest::Client,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let futures: Vec<_> = reqs
-        .par_iter()
-        .map(|req| client.post("http://localhost:8765").json(&req).send())
-        .collect();
-
-    let results = join_all(futures).await;
-
-    for result in results {
-        let res = result?;
-        let res: ReqResult = res.json().await?;
-
-        match res {
-            ReqResult {
-                result: Some(_),
-                error: None,
-            } => {}
-            ReqResult {
-                result: None,
-                error: Some(err),
-            } => return Err(err.into()),
-            _ => return Ok(()),
-        }
-    }
+    anki: &AnkiConnectConfig,
+) -> Vec<Result<(), String>> {
+    // `updateNoteFields` returns null on success
+    anki_multi::<_, serde_json::Value>(client, anki, reqs)
+        .await
+        .into_iter()
+        .map(|res| res.map(|_| ()))
+        .collect()
+}
 
-    Ok(())
+/// Posts the `addNote` requests in batches, returning the new note's id or the error for each word.
+async fn post_new_notes(
+    reqs: Vec<(String, Request<AddNoteParams>)>,
+    client: &reqwest::Client,
+    anki: &AnkiConnectConfig,
+) -> Vec<(String, Result<u128, String>)> {
+    let (words, reqs): (Vec<String>, Vec<_>) = reqs.into_iter().unzip();
+    let results = anki_multi::<_, u128>(client, anki, reqs).await;
+    words
+        .into_iter()
+        .zip(results)
+        .map(|(dict_word, res)| {
+            let id = res
+                .and_then(|id| id.ok_or_else(|| "AnkiConnect didn't return a note id".to_string()));
+            (dict_word, id)
+        })
+        .collect()
 }
 
 fn format_sentence_field(field_name: &str, ik_sentence: &str) -> HashMap<String, String> {
@@ -360,152 +1157,315 @@ fn format_sentence_field(field_name: &str, ik_sentence: &str) -> HashMap<String,
     map
 }
 
-fn format_local_audio_field(field_name: &str, url: &str) -> HashMap<String, String> {
+/// The optional fields that are mapped in the config & have a value for this sentence.
+/// `if_empty` fields are only included if the note's field is known to be empty.
+fn format_extra_fields(
+    anki_fields: &UserNoteFields,
+    sentence: &AnkiSentence,
+) -> HashMap<String, String> {
+    let sentence_obj = &sentence.sentence_obj;
+    let exp = &sentence_obj.parent_expression;
+    let non_empty = |values: &[String], sep: &str| (!values.is_empty()).then(|| values.join(sep));
+
+    let values = [
+        (&anki_fields.reading, non_empty(&exp.readings, "・")),
+        (&anki_fields.definition, non_empty(&exp.definitions, "<br>")),
+        (&anki_fields.translation, sentence_obj.translation.clone()),
+        (&anki_fields.furigana, sentence_obj.furigana.clone()),
+        (
+            &anki_fields.media_title,
+            Some(sentence_obj.media_title.clone()),
+        ),
+        (
+            &anki_fields.source_episode,
+            Some(sentence_obj.source_episode()),
+        ),
+        (&anki_fields.pitch, pitches_html(&exp.pitches)),
+        (
+            &anki_fields.frequency,
+            exp.frequency_rank().map(|rank| rank.to_string()),
+        ),
+    ];
+
+    values
+        .into_iter()
+        .filter_map(|(mapping, value)| {
+            let field_name = mapping.name();
+            if field_name.is_empty() {
+                return None;
+            }
+            if mapping.policy() == FieldPolicy::IfEmpty {
+                // a new note has no fields yet, so they are all filled
+                let current = sentence.current_fields.as_ref()?.get(field_name);
+                if current.is_some_and(|value| !value.trim().is_empty()) {
+                    return None;
+                }
+            }
+            Some((field_name.to_string(), value?))
+        })
+        .collect()
+}
+
+/// The sentence audio & image fields, pointing at the files stored in Anki's media folder.
+fn format_media_fields(
+    anki_fields: &UserNoteFields,
+    sentence: &AnkiSentence,
+) -> HashMap<String, String> {
     let mut map = HashMap::new();
-    map.insert(field_name.to_string(), url.to_string());
+    if let Some(audio) = &sentence.audio {
+        map.insert(
+            anki_fields.sentence_audio.clone(),
+            format!("[sound:{}]", audio),
+        );
+    }
+    if let Some(picture) = &sentence.picture {
+        map.insert(
+            anki_fields.image.clone(),
+            format!("<img src=\"{}\">", picture),
+        );
+    }
     map
 }
 
-fn write_audio_bytes_file(
-    media_dir: &str,
-    filename: &str,
-    bytes: &Vec<u8>,
-) -> std::io::Result<String> {
-    let media_dir = Path::new(&media_dir);
-    let file_path = media_dir.join(filename);
-    std::fs::write(file_path, bytes).unwrap();
-    let audio_url = format!("[sound:{}]", filename);
-    Ok(audio_url)
+/// `name` with part of the file's hash, so files with the same name from different decks
+/// don't overwrite each other.
+fn unique_media_name(name: &str, bytes: &[u8]) -> String {
+    let hash = format!("{:x}", Sha256::digest(bytes));
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}_{}.{}", stem, &hash[..12], ext),
+        None => format!("{}_{}", name, &hash[..12]),
+    }
+}
+
+/// Downloads the sentence's audio & image, naming them after their hash.
+async fn fetch_sentence_media(cache: &Cache, sentence: &mut AnkiSentence) -> Result<(), String> {
+    let sentence_obj = &sentence.sentence_obj;
+    let dict_word = &sentence_obj.parent_expression.dict_word;
+    let err = |err: AnkiError| format!("Err Downloading Media For {}: {}", dict_word, err);
+
+    if let Some(filename) = sentence_obj.audio_file_name() {
+        let bytes = sentence_obj.load_audio(cache).await.map_err(err)?;
+        sentence.audio = Some(unique_media_name(&filename, &bytes));
+        sentence.audio_bytes = Some(bytes);
+    }
+
+    if let Some(img_url) = &sentence_obj.img_url {
+        let bytes = cache
+            .fetch_bytes("images", img_url, img_url)
+            .await
+            .map_err(err)?;
+        sentence.picture = Some(unique_media_name(&url_into_file_name(img_url), &bytes));
+        sentence.picture_bytes = Some(bytes);
+    }
+
+    Ok(())
+}
+
+/// Uploads the downloaded audio & image with `storeMediaFile`, a file that fails isn't linked.
+async fn store_sentence_media(
+    client: &reqwest::Client,
+    url: &str,
+    sentence: &mut AnkiSentence,
+) -> Result<(), String> {
+    let audio_bytes = sentence.audio_bytes.take();
+    let audio = store_fetched_file(client, url, &mut sentence.audio, audio_bytes).await;
+    let picture_bytes = sentence.picture_bytes.take();
+    let picture = store_fetched_file(client, url, &mut sentence.picture, picture_bytes).await;
+
+    let dict_word = &sentence.sentence_obj.parent_expression.dict_word;
+    audio
+        .and(picture)
+        .map_err(|err| format!("Err Storing Media For {}: {}", dict_word, err))
+}
+
+/// Stores `bytes` under `name`, which is set to the name Anki stored it under, or cleared.
+async fn store_fetched_file(
+    client: &reqwest::Client,
+    url: &str,
+    name: &mut Option<String>,
+    bytes: Option<Vec<u8>>,
+) -> Result<(), AnkiError> {
+    let (Some(planned), Some(bytes)) = (name.take(), bytes) else {
+        return Ok(());
+    };
+    *name = Some(store_media_file(client, url, &planned, &bytes).await?);
+    Ok(())
 }
 
-fn into_update_only_sentence_req(
+fn into_update_note_req(
     id: u128,
     anki_fields: &UserNoteFields,
     sentence: &AnkiSentence,
 ) -> Request<UpdateNoteParams> {
-    let sentence_field =
-        format_sentence_field(&anki_fields.sentence, &sentence.sentence_obj.sentence);
-    let note = Note {
-        id,
-        fields: { sentence_field },
-        audio: None,
-        picture: None,
-    };
-
-    let params = UpdateNoteParams { note };
+    let mut fields = format_sentence_field(&anki_fields.sentence, &sentence.sentence_obj.sentence);
+    fields.extend(format_media_fields(anki_fields, sentence));
+    fields.extend(format_extra_fields(anki_fields, sentence));
 
     Request {
         action: "updateNoteFields".to_string(),
         version: 6,
-        params,
+        params: UpdateNoteParams {
+            note: Note { id, fields },
+        },
     }
 }
 
-fn into_update_note_req(
-    id: u128,
-    anki_fields: &UserNoteFields,
-    sentence: AnkiSentence,
-    filename: String,
-) -> Request<UpdateNoteParams> {
-    let sentence_field =
-        format_sentence_field(&anki_fields.sentence, &sentence.sentence_obj.sentence);
-
-    let picture: Option<Vec<Media>> = match &sentence.sentence_obj.img_url {
-        Some(img_url) => vec![Media {
-            url: img_url.clone(),
-            filename: url_into_file_name(img_url),
-            skipHash: None,
-            fields: vec![anki_fields.image.clone()],
-        }]
-        .into(),
-        None => None,
+/// An `addNote` request with the expression, the sentence, its media & every mapped optional field.
+fn into_add_note_req(
+    config: &ConfigJson,
+    sentence: &AnkiSentence,
+) -> (String, Request<AddNoteParams>) {
+    let anki_fields = &config.fields;
+    let dict_word = sentence
+        .sentence_obj
+        .parent_expression
+        .dict_word
+        .trim()
+        .to_string();
+    let update = into_update_note_req(0, anki_fields, sentence);
+    let mut fields = update.params.note.fields;
+    fields.insert(anki_fields.expression.clone(), dict_word.clone());
+
+    let note = NewNote {
+        deckName: config.new_notes.deck.clone(),
+        modelName: config.new_notes.model.clone(),
+        fields,
+        tags: config.new_notes.tags.clone(),
+        options: AddNoteOptions {
+            allowDuplicate: false,
+        },
     };
 
-    let local_audio_field: Option<HashMap<String, String>> =
-        sentence.local_audio_url.map(|local_audio_url| {
-            format_local_audio_field(&anki_fields.sentence_audio, &local_audio_url)
-        });
-
-    let mut note: Note = match picture {
-        Some(picture) => Note {
-            id,
-            fields: { sentence_field },
-            audio: None,
-            picture: Some(picture),
-        },
-        None => Note {
-            id,
-            fields: { sentence_field },
-            audio: None,
-            picture,
-        },
+    let req = Request {
+        action: "addNote".to_string(),
+        version: 6,
+        params: AddNoteParams { note },
     };
+    (dict_word, req)
+}
 
-    if let Some(audio_field) = local_audio_field {
-        note.fields.extend(audio_field);
-    } else {
-        note.audio = Some(vec![Media {
-            url: sentence.sentence_obj.audio_url.unwrap(),
-            filename,
-            skipHash: None,
-            fields: vec![anki_fields.sentence_audio.clone()],
-        }]);
+/// The expression's text, without the html around it.
+pub fn expression_text(html: &str) -> String {
+    let re = regex::Regex::new(r">([^<]+)<").unwrap();
+    let text: String = re
+        .captures_iter(html)
+        .map(|cap| cap[1].to_string())
+        .collect();
+    match text.trim() {
+        "" => html.trim().to_string(),
+        text => text.to_string(),
     }
+}
 
-    let params = UpdateNoteParams { note };
+/// Searches the expression field for `word` & each dictionary form it may be an inflection of,
+/// ie. `食べた` also searches for `食べる`.
+pub fn expression_query(config: &ConfigJson, word: &str) -> String {
+    // anki's wildcards & quotes, https://docs.ankiweb.net/searching.html
+    let escape = |text: &str| {
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('*', "\\*")
+            .replace('_', "\\_")
+    };
+    let field = escape(&config.fields.expression);
 
-    Request {
-        action: "updateNoteFields".to_string(),
-        version: 6,
-        params,
+    let mut terms: Vec<String> = Vec::new();
+    for d in deinflect(word.trim()) {
+        if !terms.contains(&d.term) {
+            terms.push(d.term);
+        }
     }
+    let clauses: Vec<String> = terms
+        .iter()
+        // the field may have html around the expression, `match_note` checks it's the same word
+        .map(|term| format!("\"{}:*{}*\"", field, escape(term)))
+        .collect();
+    format!("({})", clauses.join(" OR "))
 }
 
-pub async fn check_note_exists(
-    client: &AnkiDirectClient,
-    current_exp: &str,
-) -> Result<u128, Box<dyn std::error::Error>> {
-    let config = read_config()?;
-
-    let note_id = direct_find_note_from_word(client, current_exp).await?;
-    let note_infos = NoteAction::get_notes_infos(client, vec![note_id]).await?;
-
-    let doesnt_exist: String = note_infos
-        .par_iter()
-        .filter_map(|n| {
-            let exp_html = match n.fields.get(&config.fields.expression) {
-                Some(html) => html,
-                None => {
-                    return Some(format!(
-                    "Incorrect Field: `{}`; `expression` field in config has to match Anki Note!",
-                    &config.fields.expression
-                ))
-                }
-            };
-            let re = regex::Regex::new(r">([^<]+)<").unwrap();
-            let text = &exp_html.value.trim();
-
-            let mut result = String::new();
-            for cap in re.captures_iter(text) {
-                result.push_str(&cap[1]);
-            }
+/// Finds each word's note, whose expression field is the word or one of its inflections.
+/// The searches go out in `multi` requests, followed by one `notesInfo` for all the notes found.
+pub async fn find_notes(
+    client: &reqwest::Client,
+    config: &ConfigJson,
+    words: &[String],
+) -> Vec<Result<NoteInfo, NoteOutcome>> {
+    let anki = &config.anki_connect;
+    let reqs = words
+        .iter()
+        .map(|word| Request {
+            action: "findNotes".to_string(),
+            version: 6,
+            params: FindNotesParams {
+                query: expression_query(config, word),
+            },
+        })
+        .collect();
+    let found: Vec<Result<Vec<u128>, String>> = anki_multi(client, anki, reqs)
+        .await
+        .into_iter()
+        .map(|ids| ids.map(Option::unwrap_or_default))
+        .collect();
 
-            let current_exp = current_exp.trim();
-            let result = result.trim();
+    let mut ids: Vec<u128> = found.iter().flatten().flatten().copied().collect();
+    ids.sort();
+    ids.dedup();
+    let infos: Result<HashMap<u128, NoteInfo>, String> = if ids.is_empty() {
+        Ok(HashMap::new())
+    } else {
+        notes_info(client, &anki.url, ids.clone())
+            .await
+            .map(|infos| ids.into_iter().zip(infos).collect())
+            .map_err(|err| err.to_string())
+    };
 
-            if current_exp == *text || current_exp == result {
-                return None;
+    found
+        .into_iter()
+        .zip(words)
+        .map(|(ids, word)| {
+            let ids = ids.map_err(NoteOutcome::Failed)?;
+            if ids.is_empty() {
+                return Err(NoteOutcome::NoNote);
             }
+            let infos = infos
+                .as_ref()
+                .map_err(|err| NoteOutcome::Failed(err.clone()))?;
+            match_note(config, word, &ids, infos)
+        })
+        .collect()
+}
 
-            Some(format!("Can't find `{}` in any decks!", current_exp))
+/// Picks the note out of the search's `ids` whose expression field is `word`.
+fn match_note(
+    config: &ConfigJson,
+    word: &str,
+    ids: &[u128],
+    infos: &HashMap<u128, NoteInfo>,
+) -> Result<NoteInfo, NoteOutcome> {
+    let word = word.trim();
+    // the note may have the expression inflected, or the other way around
+    let same_word = |a: &str, b: &str| is_inflection_of(a, b) || is_inflection_of(b, a);
+    let with_field: Vec<(u128, String)> = ids
+        .iter()
+        .filter_map(|id| {
+            let field = infos.get(id)?.fields.get(&config.fields.expression)?;
+            Some((*id, expression_text(&field.value)))
         })
         .collect();
-
-    if !doesnt_exist.is_empty() {
-        return Err(doesnt_exist.into());
+    if with_field.is_empty() {
+        return Err(NoteOutcome::FieldMissing(config.fields.expression.clone()));
     }
 
-    Ok(note_id)
+    let matches: Vec<u128> = with_field
+        .into_iter()
+        .filter(|(_, text)| same_word(word, text))
+        .map(|(id, _)| id)
+        .collect();
+    match matches[..] {
+        [] => Err(NoteOutcome::NoNote),
+        [id] => Ok(infos[&id].clone()),
+        _ => Err(NoteOutcome::Ambiguous(matches)),
+    }
 }
 
 pub fn read_config() -> Result<ConfigJson, std::io::Error> {
@@ -1,10 +1,25 @@
-use crate::anki::{read_config, ConfigJson};
+use crate::anki::{read_config, ConfigJson, NoteResult, UpdatePlan};
+use crate::cache::Cache;
+use crate::deinflect::find_inflected;
+use crate::filters::{Facet, SentenceFilters};
+use crate::frequency::FrequencyConfig;
+use crate::jmdict::Dictionary;
+use crate::journal::JournalBatch;
 use crate::keybinds::Keybinds;
+use crate::known::KnownWords;
+use crate::prefetch::Prefetcher;
+use crate::sources::subtitles::AudioClip;
+use crate::sources::{SourceError, SourceRegistry, SourceResult};
+use crate::tasks::TaskQueue;
+use crate::tokenizer::{Token, Tokenizer};
+use crate::yomitan::{Frequency, Pitch, YomitanStore};
 use anki_direct::AnkiClient;
 use crossterm::event;
 use crossterm::event::{Event, KeyCode, KeyEventKind};
 use ratatui::{prelude::*, widgets::*};
 use std::io;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Default, PartialEq)]
 pub enum Pages {
@@ -12,6 +27,10 @@ pub enum Pages {
     Main,
     Help,
     Splice,
+    Filters,
+    Results,
+    Preview,
+    History,
 }
 
 #[derive(Default, PartialEq)]
@@ -55,6 +74,24 @@ pub(crate) struct Sentence {
     pub wbst_link: String,
     pub parent_expression: Expression,
     pub note_id: Option<u128>,
+    pub translation: Option<String>,
+    pub timestamps: Option<(Duration, Duration)>,
+    /// Local media the audio is cut from once it's played or added to Anki.
+    pub clip: Option<AudioClip>,
+    /// Anki style furigana, ie. `日本[にほん]`.
+    pub furigana: Option<String>,
+    pub episode: Option<String>,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    /// The sentence split into words, `word_index` points to the ones matching the expression.
+    pub words: Vec<String>,
+    pub word_index: Vec<usize>,
+    /// Byte range of the expression in the sentence, inflected or not.
+    pub highlight: Option<(usize, usize)>,
+    /// Empty until the tokenizer is loaded.
+    pub tokens: Vec<Token>,
+    /// Words not in the known-words set, `None` until it's loaded.
+    pub unknown_words: Option<usize>,
 }
 
 #[derive(Clone, Debug, PartialEq, Default)]
@@ -65,8 +102,12 @@ pub(crate) struct Expression {
     pub sentences_state: ListState,
     pub selected_sentence: Option<usize>,
     pub definitions: Vec<String>,
-    pub exact_search: bool,
     pub note_id: Option<u128>,
+    /// All fetched sentences, `sentences` only has the ones passing the filters.
+    pub unfiltered_sentences: Vec<Sentence>,
+    pub facets: Vec<Facet>,
+    pub frequencies: Vec<Frequency>,
+    pub pitches: Vec<Pitch>,
 }
 
 #[derive(Clone, Debug, PartialEq, Default)]
@@ -89,6 +130,25 @@ pub(crate) struct AppState {
     pub notes_to_be_created: NotesToBeCreated,
     pub client: AnkiClient,
     pub config: ConfigJson,
+    pub sources: SourceRegistry,
+    pub cache: Cache,
+    pub tasks: TaskQueue,
+    pub prefetcher: Prefetcher,
+    pub filters: SentenceFilters,
+    pub filters_state: ListState,
+    /// Outcomes of the last update, shown on the results page.
+    pub update_results: Vec<NoteResult>,
+    pub results_state: ListState,
+    /// An update waiting to be approved on the preview page.
+    pub update_plan: Option<UpdatePlan>,
+    pub plan_state: ListState,
+    /// The undo journal's batches, the newest first.
+    pub update_history: Vec<JournalBatch>,
+    pub history_state: ListState,
+    pub known_words: KnownWords,
+    pub dictionary: Option<Arc<Dictionary>>,
+    pub yomitan: Option<Arc<YomitanStore>>,
+    pub tokenizer: Option<Arc<Tokenizer>>,
 }
 
 impl AppState {
@@ -106,6 +166,9 @@ impl AppState {
             errors.push(err);
         }
 
+        let (sources, source_errs) = SourceRegistry::from_config(&config);
+        errors.extend(source_errs);
+
         Self {
             expressions: Vec::new(),
             expressions_state: ListState::default(),
@@ -118,7 +181,23 @@ impl AppState {
             selected_page: Pages::Main,
             notes_to_be_created: NotesToBeCreated::default(),
             client: AnkiClient::default(),
+            cache: Cache::new(&config.cache),
+            prefetcher: Prefetcher::new(&config.prefetch),
+            filters: SentenceFilters::from_config(&config),
+            filters_state: ListState::default(),
+            update_results: Vec::new(),
+            results_state: ListState::default(),
+            update_plan: None,
+            plan_state: ListState::default(),
+            update_history: Vec::new(),
+            history_state: ListState::default(),
+            known_words: KnownWords::default(),
+            dictionary: None,
+            yomitan: None,
+            tokenizer: None,
             config,
+            sources,
+            tasks: TaskQueue::default(),
         }
     }
 }
@@ -136,8 +215,14 @@ impl AppState {
                 .errors
                 .push(format!("Error Reading `words.txt`: {}", err)),
         }
+        self.prefetch_next();
+        self.spawn_load_known_words();
+        self.spawn_load_dictionary();
+        self.spawn_load_yomitan();
+        self.spawn_load_tokenizer();
 
         loop {
+            self.handle_task_results();
             self.draw(&mut term)?;
 
             if event::poll(std::time::Duration::from_millis(5))? {
@@ -186,8 +271,72 @@ impl Sentence {
             wbst_link: wbst_link.to_string(),
             parent_expression: parent_expression.clone(),
             note_id: None,
+            translation: None,
+            timestamps: None,
+            clip: None,
+            furigana: None,
+            episode: None,
+            category: None,
+            tags: Vec::new(),
+            words: Vec::new(),
+            word_index: Vec::new(),
+            highlight: find_inflected(sentence, &parent_expression.dict_word),
+            tokens: Vec::new(),
+            unknown_words: None,
+        }
+    }
+
+    /// The media title followed by the episode, if there is one.
+    pub fn source_episode(&self) -> String {
+        match &self.episode {
+            Some(episode) => format!("{} - Episode {}", self.media_title, episode),
+            None => self.media_title.clone(),
         }
     }
+
+    pub fn matched_words(&self) -> Vec<&str> {
+        self.word_index
+            .iter()
+            .filter_map(|&i| self.words.get(i).map(|w| w.as_str()))
+            .collect()
+    }
+    /// The sentence's audio, downloading it or cutting its clip if it isn't loaded yet.
+    pub async fn load_audio(&self, cache: &Cache) -> Result<Vec<u8>, SourceError> {
+        match (&self.audio_data, &self.clip, &self.audio_url) {
+            (Some(audio_data), _, _) => Ok(audio_data.clone()),
+            (None, Some(clip), _) => clip.cut().await,
+            (None, None, Some(audio_url)) => cache.fetch_bytes("audio", audio_url, audio_url).await,
+            (None, None, None) => Err("Audio URL not found".into()),
+        }
+    }
+
+    /// The media file name used for the sentence's audio in Anki.
+    /// Clips cut from local media don't have a url, so their name is made from the media title & timestamps.
+    pub fn audio_file_name(&self) -> Option<String> {
+        if let Some(audio_url) = &self.audio_url {
+            return audio_url
+                .rsplit_once('/')
+                .map(|(_, name)| name.to_string());
+        }
+
+        if self.audio_data.is_none() && self.clip.is_none() {
+            return None;
+        }
+        let title: String = self
+            .media_title
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let (start, end) = self.timestamps.unwrap_or_default();
+
+        Some(format!(
+            "anki_helper_{}_{}-{}.mp3",
+            title,
+            start.as_millis(),
+            end.as_millis()
+        ))
+    }
+
     pub fn to_be_created_list_item(&self, sentence: &Sentence, i: usize) -> ListItem {
         let mixed_line = Line::from(vec![
             //Span::styled("|", Color::Green),
@@ -214,18 +363,64 @@ impl Expression {
             sentences_state: ListState::default(),
             selected_sentence: Some(0),
             definitions: Vec::new(),
-            exact_search: false,
             note_id,
+            unfiltered_sentences: Vec::new(),
+            facets: Vec::new(),
+            frequencies: Vec::new(),
+            pitches: Vec::new(),
         }
     }
 
-    pub fn to_list_item(&self, i: usize) -> ListItem {
-        let mixed_line = Line::from(vec![
+    pub fn apply_source_result(
+        &mut self,
+        res: SourceResult,
+        filters: &SentenceFilters,
+        known: &KnownWords,
+    ) {
+        // dictionary definitions are kept over the source's
+        if self.definitions.is_empty() {
+            self.definitions = res.definitions;
+        }
+        for reading in res.readings {
+            if !self.readings.contains(&reading) {
+                self.readings.push(reading);
+            }
+        }
+        self.facets = res.facets;
+        self.unfiltered_sentences = res.sentences;
+        if let Some(reading) = self.readings.first() {
+            for sentence in self.unfiltered_sentences.iter_mut() {
+                sentence.furigana_from_reading(reading);
+            }
+        }
+        self.sentences = Some(Vec::new());
+        self.rank_sentences(known);
+        self.apply_filters(filters);
+    }
+
+    pub fn to_list_item(
+        &self,
+        i: usize,
+        spinner: Option<&'static str>,
+        frequency: &FrequencyConfig,
+    ) -> ListItem {
+        let mut mixed_line = Line::from(vec![
             //Span::styled("|", Color::Green),
             Span::styled(i.to_string(), Style::default().yellow()),
             Span::styled(". ", Color::Green),
             Span::styled(&self.dict_word, Color::White),
         ]);
+        if let Some(rank) = self.frequency_rank() {
+            let style = if frequency.is_above_threshold(Some(rank)) {
+                Style::default().red()
+            } else {
+                Style::default().dim()
+            };
+            mixed_line.push_span(Span::styled(format!(" #{}", rank), style));
+        }
+        if let Some(spinner) = spinner {
+            mixed_line.push_span(Span::styled(format!(" {}", spinner), Color::LightCyan));
+        }
 
         ListItem::new(mixed_line)
     }
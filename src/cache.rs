@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+pub type CacheError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The cache is walked for eviction once `max_size_mb / EVICT_SLACK` was written since the last walk.
+const EVICT_SLACK: u64 = 16;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub dir: String,
+    pub max_size_mb: u64,
+    pub ttl_hours: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: "data/cache".to_string(),
+            max_size_mb: 256,
+            ttl_hours: 24 * 7,
+        }
+    }
+}
+
+/// On-disk cache of fetched responses & audio, stored under `dir/<kind>/<sha256(kind + key)>`.
+///
+/// Entries older than `ttl_hours` are refetched, but are still used when the network is down.
+/// The oldest entries are removed once the cache grows past `max_size_mb`, checked on the first write
+/// & then every `EVICT_SLACK`th of it written.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    config: CacheConfig,
+    /// Bytes written since the cache was last walked for eviction.
+    written: Arc<Mutex<u64>>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(&CacheConfig::default())
+    }
+}
+
+impl Cache {
+    pub fn new(config: &CacheConfig) -> Self {
+        Self {
+            config: config.clone(),
+            written: Arc::new(Mutex::new(u64::MAX)),
+        }
+    }
+
+    fn path(&self, kind: &str, key: &str) -> PathBuf {
+        let hash = Sha256::digest(format!("{}\0{}", kind, key));
+        Path::new(&self.config.dir)
+            .join(kind)
+            .join(format!("{:x}", hash))
+    }
+
+    pub async fn get(&self, kind: &str, key: &str) -> Option<Vec<u8>> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        let path = self.path(kind, key);
+        let age = tokio::fs::metadata(&path)
+            .await
+            .ok()?
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .unwrap_or_default();
+
+        if age > Duration::from_secs(self.config.ttl_hours * 60 * 60) {
+            return None;
+        }
+
+        tokio::fs::read(path).await.ok()
+    }
+
+    /// Same as `get`, ignoring the ttl.
+    pub async fn get_stale(&self, kind: &str, key: &str) -> Option<Vec<u8>> {
+        if !self.config.enabled {
+            return None;
+        }
+        tokio::fs::read(self.path(kind, key)).await.ok()
+    }
+
+    pub async fn put(&self, kind: &str, key: &str, bytes: &[u8]) -> Result<(), CacheError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let path = self.path(kind, key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, bytes).await?;
+
+        let max_bytes = self.config.max_size_mb * 1024 * 1024;
+        {
+            let mut written = self.written.lock().unwrap();
+            *written = written.saturating_add(bytes.len() as u64);
+            if *written < max_bytes / EVICT_SLACK {
+                return Ok(());
+            }
+            *written = 0;
+        }
+
+        let dir = PathBuf::from(&self.config.dir);
+        tokio::task::spawn_blocking(move || evict_oldest(&dir, max_bytes)).await??;
+        Ok(())
+    }
+
+    /// Returns the cached bytes for `kind` + `key`, fetching them from `url` on a miss.
+    /// Falls back to an expired entry if the request fails.
+    pub async fn fetch_bytes(
+        &self,
+        kind: &str,
+        key: &str,
+        url: &str,
+    ) -> Result<Vec<u8>, CacheError> {
+        self.fetch_bytes_with(kind, key, url, |_| true).await
+    }
+
+    /// Same as `fetch_bytes`, but only caches & uses entries `is_valid` accepts, so an error page
+    /// isn't served for the whole ttl. An invalid response is still returned for the caller to
+    /// report, if there's no older entry to fall back to.
+    pub async fn fetch_bytes_with(
+        &self,
+        kind: &str,
+        key: &str,
+        url: &str,
+        is_valid: impl Fn(&[u8]) -> bool,
+    ) -> Result<Vec<u8>, CacheError> {
+        if let Some(bytes) = self.get(kind, key).await.filter(|b| is_valid(b)) {
+            return Ok(bytes);
+        }
+
+        let resp = match reqwest::get(url).await.and_then(|r| r.error_for_status()) {
+            Ok(resp) => resp.bytes().await.map(|b| b.to_vec()),
+            Err(err) => Err(err),
+        };
+
+        match resp {
+            Ok(bytes) if is_valid(&bytes) => {
+                // a full disk shouldn't stop the fetch
+                let _ = self.put(kind, key, &bytes).await;
+                Ok(bytes)
+            }
+            Ok(bytes) => Ok(self
+                .get_stale(kind, key)
+                .await
+                .filter(|b| is_valid(b))
+                .unwrap_or(bytes)),
+            Err(err) => match self.get_stale(kind, key).await.filter(|b| is_valid(b)) {
+                Some(bytes) => Ok(bytes),
+                None => Err(err.into()),
+            },
+        }
+    }
+}
+
+fn evict_oldest(dir: &Path, max_bytes: u64) -> std::io::Result<()> {
+    let mut entries: Vec<(PathBuf, u64, SystemTime)> = Vec::new();
+    collect_entries(dir, &mut entries)?;
+
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    if total <= max_bytes {
+        return Ok(());
+    }
+
+    entries.sort_by_key(|(_, _, modified)| *modified);
+    for (path, size, _) in entries {
+        if total <= max_bytes {
+            break;
+        }
+        std::fs::remove_file(path)?;
+        total = total.saturating_sub(size);
+    }
+    Ok(())
+}
+
+fn collect_entries(
+    dir: &Path,
+    entries: &mut Vec<(PathBuf, u64, SystemTime)>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            collect_entries(&entry.path(), entries)?;
+        } else {
+            entries.push((
+                entry.path(),
+                meta.len(),
+                meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            ));
+        }
+    }
+    Ok(())
+}
@@ -1,11 +1,13 @@
 use crate::anki::return_new_anki_words;
 use crate::app::*;
+use crate::sources::SourceResult;
+use crate::tasks::{TaskKind, TaskResult};
 use arboard::Clipboard;
 use rayon::prelude::*;
 use std::fs::{File, OpenOptions};
 use std::io::{self, prelude::*, BufReader, BufWriter};
 use std::process::Command;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 impl AppState {
     pub fn get_current_sentence(&self) -> Option<Sentence> {
@@ -121,8 +123,7 @@ impl AppState {
             let line = line?;
 
             for word in line.split_whitespace() {
-                self.expressions
-                    .push(Expression::from(word.to_string(), None, None, None));
+                self.push_expression(Expression::from(word.to_string(), None, None, None));
             }
         }
 
@@ -133,7 +134,7 @@ impl AppState {
                         if self.expressions.contains(&exp) {
                             continue;
                         }
-                        self.expressions.push(exp);
+                        self.push_expression(exp);
                     }
                 }
                 Err(e) => {
@@ -145,55 +146,92 @@ impl AppState {
         Ok(())
     }
 
-    pub async fn fetch_massif_sentences(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn fetch_sentences(&mut self, skip_ik: bool) {
         if let Some(i) = self.selected_expression {
-            let current_word = self.expressions[i].dict_word.clone();
-            let format_url = format!("https://massif.la/ja/search?q={}&fmt=json", &current_word);
-            self.fetch_massif_api(self.expressions[i].clone(), i, format_url)
-                .await?;
+            let expression = self.expressions[i].clone();
+            let dict_word = expression.dict_word.clone();
+            let sources = self.sources.clone();
+
+            self.info.msg = format!("Fetching Sentences For {}", &dict_word).into();
+            if !skip_ik && self.tasks.loading_words().contains(&dict_word) {
+                // already being prefetched
+                return;
+            }
+            self.tasks.cancel(TaskKind::Sentences, Some(&dict_word));
+
+            self.tasks
+                .spawn(TaskKind::Sentences, Some(dict_word.clone()), async move {
+                    let instant = Instant::now();
+                    let result = sources
+                        .fetch(&expression, skip_ik)
+                        .await
+                        .map_err(|err| err.to_string());
+
+                    TaskResult::Sentences {
+                        dict_word,
+                        elapsed: instant.elapsed(),
+                        result,
+                        prefetched: false,
+                    }
+                });
         }
-        Ok(())
     }
 
-    pub async fn fetch_sentences(&mut self, is_massif: bool) {
-        if let Some(i) = self.selected_expression {
-            let instant = Instant::now();
-            let current_word = self.expressions[i].dict_word.clone();
-
-            let format_url = format!(
-                "https://api.immersionkit.com/look_up_dictionary?keyword={}&sort=shortness",
-                &current_word
-            );
-
-            match self
-                .fetch_ik_api(self.expressions[i].clone(), i, format_url, is_massif)
-                .await
-            {
-                Ok(_) => {
-                    if self.expressions[i].exact_search {
-                        self.info.msg = format!(
-                            "Fetched Massif Sentences for {} in {}s",
-                            &current_word,
-                            instant.elapsed().as_secs()
-                        )
-                        .into();
-                    } else {
-                        self.info.msg = format!(
-                            "Fetched Sentences For {} in {}s",
-                            &current_word,
-                            instant.elapsed().as_secs()
-                        )
-                        .into();
+    pub fn apply_fetched_sentences(
+        &mut self,
+        dict_word: &str,
+        elapsed: Duration,
+        result: Result<(SourceResult, &'static str), String>,
+        prefetched: bool,
+    ) {
+        let Some(i) = self
+            .expressions
+            .iter()
+            .position(|e| e.dict_word == dict_word)
+        else {
+            return;
+        };
+        // prefetches are only reported if they're being waited on
+        let is_waiting =
+            self.selected_expression == Some(i) && self.select_mode == SelectMode::Sentences;
+
+        match result {
+            Ok((mut res, label)) => {
+                if let Some(tokenizer) = &self.tokenizer {
+                    for sentence in res.sentences.iter_mut() {
+                        sentence.apply_tokenizer(tokenizer);
                     }
                 }
-                Err(err) => {
+                self.expressions[i].apply_source_result(res, &self.filters, &self.known_words);
+
+                if !prefetched || is_waiting {
+                    let hidden = self.expressions[i].hidden_sentences();
+                    let mut msg = format!(
+                        "Fetched {} Sentences For {} in {}s",
+                        label,
+                        dict_word,
+                        elapsed.as_secs()
+                    );
+                    if hidden > 0 {
+                        msg.push_str(&format!(" ({} hidden by filters, <F>)", hidden));
+                    }
+                    self.info.msg = Some(msg);
+                }
+            }
+            Err(_) if prefetched && !is_waiting => {}
+            Err(err) => {
+                if is_waiting {
                     self.select_mode = SelectMode::Expressions;
-                    self.info.msg = None;
-                    self.errors
-                        .push(format!("Error Fetching {}: {}", &current_word, err));
                 }
+                self.info.msg = None;
+                self.errors
+                    .push(format!("Error Fetching {}: {}", dict_word, err));
             }
         }
+
+        if !prefetched {
+            self.prefetch_next();
+        }
     }
 
     pub fn open_website_link(&mut self) {
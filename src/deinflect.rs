@@ -0,0 +1,363 @@
+use crate::app::*;
+use aho_corasick::{AhoCorasick, MatchKind};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Word types a form can be, a rule only applies to forms of the types in its `rules_in`.
+const V1: u32 = 1;
+const V5: u32 = 1 << 1;
+const VS: u32 = 1 << 2;
+const VK: u32 = 1 << 3;
+const ADJ_I: u32 = 1 << 4;
+/// The て form before an auxiliary, ie. 食べて from 食べている.
+const TE: u32 = 1 << 5;
+
+/// Rules followed back from the dictionary form when inflecting it, enough for 食べられなかった.
+const MAX_INFLECTION_DEPTH: usize = 3;
+/// Words whose matchers are kept, the cache is cleared once it's full.
+const MAX_CACHED_MATCHERS: usize = 64;
+
+/// Replaces the inflected ending `from` with `to`, going one step back towards the dictionary form.
+struct Rule {
+    from: String,
+    to: String,
+    /// Types the inflected form has to be. A rule with none only applies to the word as it was
+    /// given, so it can't follow another rule.
+    rules_in: u32,
+    /// Type of the form it's deinflected to.
+    rules_out: u32,
+    reason: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deinflection {
+    pub term: String,
+    rules: u32,
+    /// In the order they're applied to the dictionary form, ie. `potential, negative, past`.
+    pub reasons: Vec<&'static str>,
+}
+
+/// Dictionary ending, then the あ, い, え & お row kana, the て & た forms.
+const GODAN: [(&str, &str, &str, &str, &str, &str, &str); 9] = [
+    ("う", "わ", "い", "え", "お", "って", "った"),
+    ("く", "か", "き", "け", "こ", "いて", "いた"),
+    ("ぐ", "が", "ぎ", "げ", "ご", "いで", "いだ"),
+    ("す", "さ", "し", "せ", "そ", "して", "した"),
+    ("つ", "た", "ち", "て", "と", "って", "った"),
+    ("ぬ", "な", "に", "ね", "の", "んで", "んだ"),
+    ("ぶ", "ば", "び", "べ", "ぼ", "んで", "んだ"),
+    ("む", "ま", "み", "め", "も", "んで", "んだ"),
+    ("る", "ら", "り", "れ", "ろ", "って", "った"),
+];
+
+fn rule(from: &str, to: &str, rules_in: u32, rules_out: u32, reason: &'static str) -> Rule {
+    Rule {
+        from: from.to_string(),
+        to: to.to_string(),
+        rules_in,
+        rules_out,
+        reason,
+    }
+}
+
+/// Ichidan rules come first, so they win over godan ones that deinflect to the same term.
+fn rules() -> &'static [Rule] {
+    static RULES: OnceLock<Vec<Rule>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        let mut rules = Vec::new();
+
+        // ichidan, `stem` + ending
+        for (from, rules_in, reason) in [
+            ("ない", ADJ_I, "negative"),
+            ("ます", 0, "polite"),
+            ("ません", 0, "polite negative"),
+            ("ました", 0, "polite past"),
+            ("ませんでした", 0, "polite past negative"),
+            ("ましょう", 0, "polite volitional"),
+            ("た", 0, "past"),
+            ("て", TE, "te"),
+            ("たら", 0, "conditional"),
+            ("たり", 0, "tari"),
+            ("られる", V1, "potential or passive"),
+            ("させる", V1, "causative"),
+            ("よう", 0, "volitional"),
+            ("ろ", 0, "imperative"),
+            ("よ", 0, "imperative"),
+            ("れば", 0, "provisional"),
+            ("たい", ADJ_I, "desiderative"),
+            ("ず", 0, "negative"),
+            ("ちゃう", V5, "finished"),
+        ] {
+            rules.push(rule(from, "る", rules_in, V1, reason));
+        }
+
+        for (u, a, i, e, o, te, ta) in GODAN {
+            for (from, rules_in, reason) in [
+                (format!("{a}ない"), ADJ_I, "negative"),
+                (format!("{i}ます"), 0, "polite"),
+                (format!("{i}ません"), 0, "polite negative"),
+                (format!("{i}ました"), 0, "polite past"),
+                (format!("{i}ませんでした"), 0, "polite past negative"),
+                (format!("{i}ましょう"), 0, "polite volitional"),
+                (ta.to_string(), 0, "past"),
+                (te.to_string(), TE, "te"),
+                (format!("{ta}ら"), 0, "conditional"),
+                (format!("{ta}り"), 0, "tari"),
+                (format!("{a}れる"), V1, "passive"),
+                (format!("{a}せる"), V1, "causative"),
+                (format!("{e}る"), V1, "potential"),
+                (format!("{o}う"), 0, "volitional"),
+                (e.to_string(), 0, "imperative"),
+                (format!("{e}ば"), 0, "provisional"),
+                (format!("{i}たい"), ADJ_I, "desiderative"),
+                (format!("{a}ず"), 0, "negative"),
+            ] {
+                rules.push(rule(&from, u, rules_in, V5, reason));
+            }
+            let chau = if te.ends_with('で') {
+                "じゃう"
+            } else {
+                "ちゃう"
+            };
+            let chau = format!("{}{}", te.trim_end_matches(['て', 'で']), chau);
+            rules.push(rule(&chau, u, V5, V5, "finished"));
+        }
+        // 行く is the only godan く verb with a って te form
+        for (from, rules_in, reason) in [("行って", TE, "te"), ("行った", 0, "past")] {
+            rules.push(rule(from, "行く", rules_in, V5, reason));
+            rules.push(rule(
+                &from.replace('行', "い"),
+                "いく",
+                rules_in,
+                V5,
+                reason,
+            ));
+        }
+
+        for (from, rules_in, reason) in [
+            ("しない", ADJ_I, "negative"),
+            ("します", 0, "polite"),
+            ("しません", 0, "polite negative"),
+            ("しました", 0, "polite past"),
+            ("しませんでした", 0, "polite past negative"),
+            ("しましょう", 0, "polite volitional"),
+            ("した", 0, "past"),
+            ("して", TE, "te"),
+            ("したら", 0, "conditional"),
+            ("したり", 0, "tari"),
+            ("される", V1, "passive"),
+            ("させる", V1, "causative"),
+            ("しよう", 0, "volitional"),
+            ("しろ", 0, "imperative"),
+            ("せよ", 0, "imperative"),
+            ("すれば", 0, "provisional"),
+            ("したい", ADJ_I, "desiderative"),
+            ("せず", 0, "negative"),
+        ] {
+            rules.push(rule(from, "する", rules_in, VS, reason));
+        }
+
+        // 来る is read こ, き or く depending on the ending
+        for (stem, from, rules_in, reason) in [
+            ("こ", "ない", ADJ_I, "negative"),
+            ("き", "ます", 0, "polite"),
+            ("き", "ません", 0, "polite negative"),
+            ("き", "ました", 0, "polite past"),
+            ("き", "た", 0, "past"),
+            ("き", "て", TE, "te"),
+            ("き", "たら", 0, "conditional"),
+            ("こ", "られる", V1, "potential or passive"),
+            ("こ", "させる", V1, "causative"),
+            ("こ", "よう", 0, "volitional"),
+            ("こ", "い", 0, "imperative"),
+            ("く", "れば", 0, "provisional"),
+            ("き", "たい", ADJ_I, "desiderative"),
+        ] {
+            rules.push(rule(&format!("{stem}{from}"), "くる", rules_in, VK, reason));
+            rules.push(rule(&format!("来{from}"), "来る", rules_in, VK, reason));
+        }
+
+        for (from, rules_in, reason) in [
+            ("くない", ADJ_I, "negative"),
+            ("かった", 0, "past"),
+            ("く", 0, "adverb"),
+            ("くて", TE, "te"),
+            ("ければ", 0, "provisional"),
+            ("かったら", 0, "conditional"),
+            ("さ", 0, "noun"),
+            ("そう", 0, "seemingness"),
+        ] {
+            rules.push(rule(from, "い", rules_in, ADJ_I, reason));
+        }
+
+        // auxiliaries after the て form, ie. 食べている, 食べてる, 食べてしまう
+        for (from, rules_in, reason) in [
+            ("いる", V1, "progressive"),
+            ("る", V1, "progressive"),
+            ("しまう", V5, "finished"),
+            ("おく", V5, "preparation"),
+            ("ください", 0, "request"),
+        ] {
+            rules.push(rule(from, "", rules_in, TE, reason));
+        }
+
+        rules
+    })
+}
+
+/// Every form `word` could be deinflected to, breadth first so the least inflected come first.
+/// The first one is `word` itself.
+pub fn deinflect(word: &str) -> Vec<Deinflection> {
+    let mut results = vec![Deinflection {
+        term: word.to_string(),
+        rules: 0,
+        reasons: Vec::new(),
+    }];
+    let mut seen = HashSet::new();
+
+    let mut i = 0;
+    while i < results.len() {
+        let current = results[i].clone();
+        i += 1;
+
+        for rule in rules() {
+            if current.rules != 0 && current.rules & rule.rules_in == 0 {
+                continue;
+            }
+            let Some(stem) = current.term.strip_suffix(rule.from.as_str()) else {
+                continue;
+            };
+            let term = format!("{}{}", stem, rule.to);
+            if term.is_empty() || !seen.insert((term.clone(), rule.rules_out)) {
+                continue;
+            }
+            let mut reasons = vec![rule.reason];
+            reasons.extend(&current.reasons);
+            results.push(Deinflection {
+                term,
+                rules: rule.rules_out,
+                reasons,
+            });
+        }
+    }
+
+    results
+}
+
+/// Whether `text` is `word` or one of its inflections.
+pub fn is_inflection_of(text: &str, word: &str) -> bool {
+    text == word || deinflect(text).iter().any(|d| d.term == word)
+}
+
+/// The forms of `word` that deinflect back to it, `word` included. Only goes
+/// `MAX_INFLECTION_DEPTH` rules deep, the rules can be chained forever (ie. させさせる).
+pub fn inflections(word: &str) -> Vec<String> {
+    // each form with the types the rule before it has to deinflect to, `None` for `word`
+    let mut forms: Vec<(String, Option<u32>)> = vec![(word.to_string(), None)];
+    let mut seen: HashSet<(String, Option<u32>)> = forms.iter().cloned().collect();
+
+    let mut start = 0;
+    for _ in 0..MAX_INFLECTION_DEPTH {
+        let end = forms.len();
+        for i in start..end {
+            let (form, types) = forms[i].clone();
+            for rule in rules() {
+                let follows = match types {
+                    // the auxiliaries only follow a て form
+                    None => !rule.to.is_empty(),
+                    Some(types) => types & rule.rules_out != 0,
+                };
+                if !follows {
+                    continue;
+                }
+                let Some(stem) = form.strip_suffix(rule.to.as_str()) else {
+                    continue;
+                };
+                let inflected = (format!("{}{}", stem, rule.from), Some(rule.rules_in));
+                if seen.insert(inflected.clone()) {
+                    forms.push(inflected);
+                }
+            }
+        }
+        start = end;
+    }
+
+    let mut inflections: Vec<String> = forms.into_iter().map(|(form, _)| form).collect();
+    inflections.sort();
+    inflections.dedup();
+    inflections
+}
+
+/// Finds a word, or any of its inflections, in a sentence.
+pub struct InflectionMatcher {
+    searcher: Option<AhoCorasick>,
+}
+
+impl InflectionMatcher {
+    pub fn new(word: &str) -> Self {
+        let word = word.trim();
+        let forms = if word.is_empty() {
+            Vec::new()
+        } else {
+            inflections(word)
+        };
+        let searcher = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(forms)
+            .ok();
+        Self { searcher }
+    }
+
+    /// Byte range of the first match, the longest one if several start there,
+    /// so 食べなかった isn't matched as 食べな.
+    pub fn find(&self, sentence: &str) -> Option<(usize, usize)> {
+        let found = self.searcher.as_ref()?.find(sentence)?;
+        Some((found.start(), found.end()))
+    }
+}
+
+/// The matcher for `word`, cached so the forms aren't generated again for every sentence.
+fn matcher(word: &str) -> Arc<InflectionMatcher> {
+    static MATCHERS: OnceLock<Mutex<HashMap<String, Arc<InflectionMatcher>>>> = OnceLock::new();
+    let mut matchers = MATCHERS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    if matchers.len() >= MAX_CACHED_MATCHERS && !matchers.contains_key(word) {
+        matchers.clear();
+    }
+    matchers
+        .entry(word.to_string())
+        .or_insert_with(|| Arc::new(InflectionMatcher::new(word)))
+        .clone()
+}
+
+/// Byte range of the first occurrence of `word` in `sentence`, inflected or not.
+pub fn find_inflected(sentence: &str, word: &str) -> Option<(usize, usize)> {
+    matcher(word.trim()).find(sentence)
+}
+
+impl AppState {
+    /// Whether the loaded dictionaries have an entry for `word`.
+    pub fn is_dictionary_word(&self, word: &str) -> bool {
+        self.dictionary
+            .as_ref()
+            .is_some_and(|dict| !dict.lookup(word).is_empty())
+            || self
+                .yomitan
+                .as_ref()
+                .is_some_and(|store| !store.terms(word).is_empty())
+    }
+
+    /// The least inflected dictionary form of `word`, ie. 食べる for 食べられなかった.
+    /// `None` if `word` already is one, or if none of its forms are in the loaded dictionaries.
+    pub fn dictionary_form(&self, word: &str) -> Option<Deinflection> {
+        if self.is_dictionary_word(word) {
+            return None;
+        }
+        deinflect(word)
+            .into_iter()
+            .skip(1)
+            .find(|d| d.rules != TE && self.is_dictionary_word(&d.term))
+    }
+}
@@ -109,136 +109,285 @@ struct MassifJsonSchema {
     results: Vec<MassifResults>,
 }
 
+use crate::anki::ConfigJson;
 use crate::app::*;
+use crate::cache::Cache;
+use crate::filters::Facet;
+use crate::sources::{SentenceSource, SourceError, SourceResult};
+use crate::tasks::{TaskKind, TaskResult};
+use futures_util::future::BoxFuture;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Spaces out requests to the same api by at least `interval`.
+pub struct RateLimiter {
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
 
-impl AppState {
-    pub async fn fetch_massif_api(
-        &mut self,
-        parent_expression: Expression,
-        index: usize,
-        format_url: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let resp = reqwest::get(&format_url)
-            .await?
-            .json::<MassifJsonSchema>()
-            .await?;
-
-        let sentences: Vec<Sentence> = resp
-            .results
-            .par_iter()
-            .map(|item| {
-                let wbst_link = format!("https://massif.la/ja/search?q={}", &item.text);
-                Sentence::from(
-                    &item.text,
-                    None,
-                    None,
-                    None,
-                    &item.sample_source.title,
-                    &wbst_link,
-                    &parent_expression,
-                )
-            })
-            .collect();
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Mutex::new(None),
+        }
+    }
 
-        if sentences.is_empty() {
-            return Err("0 Sentences Found!".into());
+    pub async fn wait(&self) {
+        let mut last = self.last.lock().await;
+        if let Some(elapsed) = last.map(|l| l.elapsed()) {
+            if elapsed < self.interval {
+                tokio::time::sleep(self.interval - elapsed).await;
+            }
         }
+        *last = Some(Instant::now());
+    }
+}
 
-        self.expressions[index].sentences = Some(sentences);
-        Ok(())
+pub struct ImmersionKitSource {
+    cache: Cache,
+    limiter: RateLimiter,
+}
+
+impl ImmersionKitSource {
+    pub fn new(config: &ConfigJson) -> Self {
+        Self {
+            cache: Cache::new(&config.cache),
+            limiter: RateLimiter::new(Duration::from_millis(config.prefetch.ik_min_interval_ms)),
+        }
+    }
+}
+
+impl SentenceSource for ImmersionKitSource {
+    fn name(&self) -> &'static str {
+        "immersion_kit"
     }
 
-    pub async fn fetch_ik_api(
-        &mut self,
-        parent_expression: Expression,
-        index: usize,
-        format_url: String,
-        is_massif: bool,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        if is_massif {
-            self.fetch_massif_sentences().await?;
-            return Ok(());
+    fn label(&self) -> &'static str {
+        "Immersion Kit"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        expression: &'a Expression,
+    ) -> BoxFuture<'a, Result<SourceResult, SourceError>> {
+        Box::pin(fetch_ik_api(expression, &self.cache, &self.limiter))
+    }
+}
+
+pub struct MassifSource {
+    cache: Cache,
+}
+
+impl MassifSource {
+    pub fn new(config: &ConfigJson) -> Self {
+        Self {
+            cache: Cache::new(&config.cache),
         }
+    }
+}
 
-        let resp = reqwest::get(&format_url)
-            .await?
-            .json::<IKJsonSchema>()
-            .await?;
-
-        //let mut titles: Vec<String> = Vec::new();
-        let mut sentences: Vec<Sentence> = Vec::new();
-
-        for item in resp.data {
-            for empty_vec in item.dictionary {
-                for section in empty_vec {
-                    self.expressions[index]
-                        .definitions
-                        .extend(section.glossary_list);
-
-                    if !parent_expression.readings.contains(&section.reading)
-                        && self.expressions[index].dict_word != section.reading
-                    {
-                        self.expressions[index].readings.push(section.reading);
-                    }
-                }
-            }
+impl SentenceSource for MassifSource {
+    fn name(&self) -> &'static str {
+        "massif"
+    }
 
-            let item_sents: Vec<Sentence> = item
-                .examples
-                .into_iter()
-                .filter_map(|ex| {
-                    let image_url = if !ex.image_url.is_empty() {
-                        Some(ex.image_url.to_string())
-                    } else {
-                        None
-                    };
-
-                    let wbst_link = format!(
-                        "https://www.immersionkit.com/dictionary?keyword={}",
-                        &ex.sentence
-                    );
-
-                    if self.config.priority.is_empty() {
-                        return Some(Sentence::from(
-                            &ex.sentence,
-                            Some(ex.sound_url),
-                            None,
-                            image_url,
-                            &ex.deck_name,
-                            &wbst_link,
-                            &parent_expression,
-                        ));
-                    }
+    fn label(&self) -> &'static str {
+        "Massif"
+    }
 
-                    if self.config.priority.contains(&ex.deck_name) {
-                        return Some(Sentence::from(
-                            &ex.sentence,
-                            Some(ex.sound_url),
-                            None,
-                            image_url,
-                            &ex.deck_name,
-                            &wbst_link,
-                            &parent_expression,
-                        ));
-                    }
+    fn fetch<'a>(
+        &'a self,
+        expression: &'a Expression,
+    ) -> BoxFuture<'a, Result<SourceResult, SourceError>> {
+        Box::pin(fetch_massif_api(expression, &self.cache))
+    }
+}
 
-                    None
-                })
-                .collect();
+pub async fn fetch_massif_api(
+    parent_expression: &Expression,
+    cache: &Cache,
+) -> Result<SourceResult, SourceError> {
+    let format_url = format!(
+        "https://massif.la/ja/search?q={}&fmt=json",
+        &parent_expression.dict_word
+    );
+
+    let is_valid = |b: &[u8]| serde_json::from_slice::<MassifJsonSchema>(b).is_ok();
+    let bytes = cache
+        .fetch_bytes_with(
+            "massif",
+            &parent_expression.dict_word,
+            &format_url,
+            is_valid,
+        )
+        .await?;
+    let resp: MassifJsonSchema = serde_json::from_slice(&bytes)?;
+
+    let sentences: Vec<Sentence> = resp
+        .results
+        .par_iter()
+        .map(|item| {
+            let wbst_link = format!("https://massif.la/ja/search?q={}", &item.text);
+            Sentence::from(
+                &item.text,
+                None,
+                None,
+                None,
+                &item.sample_source.title,
+                &wbst_link,
+                parent_expression,
+            )
+        })
+        .collect();
+
+    Ok(SourceResult {
+        sentences,
+        ..SourceResult::default()
+    })
+}
 
-            sentences = item_sents;
+pub async fn fetch_ik_api(
+    parent_expression: &Expression,
+    cache: &Cache,
+    limiter: &RateLimiter,
+) -> Result<SourceResult, SourceError> {
+    let format_url = format!(
+        "https://api.immersionkit.com/look_up_dictionary?keyword={}&sort=shortness",
+        &parent_expression.dict_word
+    );
+    let key = &parent_expression.dict_word;
+
+    let is_valid = |b: &[u8]| serde_json::from_slice::<IKJsonSchema>(b).is_ok();
+    let bytes = match cache.get("immersion_kit", key).await {
+        Some(bytes) if is_valid(&bytes) => bytes,
+        _ => {
+            limiter.wait().await;
+            cache
+                .fetch_bytes_with("immersion_kit", key, &format_url, is_valid)
+                .await?
         }
+    };
+    let resp: IKJsonSchema = serde_json::from_slice(&bytes)?;
+
+    let mut result = SourceResult::default();
+
+    for item in resp.data {
+        for empty_vec in item.dictionary {
+            for section in empty_vec {
+                result.definitions.extend(section.glossary_list);
 
-        if sentences.is_empty() {
-            self.fetch_massif_sentences().await?;
-            return Ok(());
+                if !result.readings.contains(&section.reading)
+                    && parent_expression.dict_word != section.reading
+                {
+                    result.readings.push(section.reading);
+                }
+            }
         }
 
-        //write_media(titles).unwrap();
-        self.expressions[index].sentences = Some(sentences);
-        Ok(())
+        result.facets = item_facets(&item.category_count, &item.deck_count);
+        result.sentences = item
+            .examples
+            .into_iter()
+            .map(|ex| {
+                let image_url = if !ex.image_url.is_empty() {
+                    Some(ex.image_url.to_string())
+                } else {
+                    None
+                };
+
+                let wbst_link = format!(
+                    "https://www.immersionkit.com/dictionary?keyword={}",
+                    &ex.sentence
+                );
+
+                let mut sentence = Sentence::from(
+                    &ex.sentence,
+                    Some(ex.sound_url),
+                    None,
+                    image_url,
+                    &ex.deck_name,
+                    &wbst_link,
+                    parent_expression,
+                );
+                sentence.translation = non_empty(ex.translation);
+                sentence.furigana = non_empty(ex.sentence_with_furigana);
+                sentence.episode = match ex.episode {
+                    StringOru64::Empty(episode) => non_empty(episode),
+                    StringOru64::Number(episode) => Some(episode.to_string()),
+                };
+                sentence.timestamps =
+                    ex.timestamp
+                        .as_deref()
+                        .and_then(parse_timestamp)
+                        .map(|start| {
+                            let end = match &ex.sound_end {
+                                StringOru64::Empty(end) => parse_timestamp(end),
+                                StringOru64::Number(_) => None,
+                            };
+                            (start, end.unwrap_or(start))
+                        });
+                sentence.category = non_empty(ex.category);
+                sentence.tags = ex.tags;
+                sentence.words = ex.word_list;
+                sentence.word_index = ex.word_index;
+
+                sentence
+            })
+            .collect();
     }
 
+    Ok(result)
+}
+
+fn item_facets(categories: &CategoryCount, decks: &DeckCount) -> Vec<Facet> {
+    let counts = [
+        ("anime", categories.anime, &decks.anime),
+        ("drama", categories.drama, &decks.drama),
+        ("games", categories.games, &decks.games),
+        ("literature", categories.literature, &decks.literature),
+        ("news", categories.news, &decks.news),
+    ];
+
+    let mut facets = Vec::new();
+    for (category, count, decks) in counts {
+        facets.push(Facet {
+            category: category.to_string(),
+            deck: None,
+            count,
+        });
+
+        let mut decks: Vec<(&String, &u16)> = decks.iter().collect();
+        decks.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        facets.extend(decks.into_iter().map(|(deck, count)| Facet {
+            category: category.to_string(),
+            deck: Some(deck.clone()),
+            count: *count,
+        }));
+    }
+    facets
+}
+
+fn non_empty(s: String) -> Option<String> {
+    if s.trim().is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+/// Parses Immersion Kit's `h:mm:ss.mmm` timestamps.
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let mut secs = 0.0;
+    for part in timestamp.trim().split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(Duration::from_secs_f64(secs))
+}
+
+impl AppState {
     // pub async fn push_audio(&mut self) -> Result<(), Box<dyn std::error::Error>> {
     //     if let Some(exp_index) = self.selected_expression {
     //         if let Some(sent_index) = self.expressions[exp_index].selected_sentence {
@@ -258,34 +407,68 @@ impl AppState {
     //     Ok(())
     // }
 
-    pub async fn play_audio(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(exp_index) = self.selected_expression {
-            if let Some(sent_index) = self.expressions[exp_index].selected_sentence {
-                let sentence =
-                    &mut self.expressions[exp_index].sentences.as_mut().unwrap()[sent_index];
+    pub fn play_audio(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let sentence = self.get_current_sentence().ok_or("No Sentence Selected")?;
+        if sentence.audio_data.is_none() && sentence.clip.is_none() && sentence.audio_url.is_none()
+        {
+            return Err("Audio URL not found".into());
+        }
 
-                let audio_data = if let Some(audio_data) = &sentence.audio_data {
-                    audio_data.clone()
-                } else {
-                    let audio_url = sentence.audio_url.clone().ok_or("Audio URL not found")?;
-                    let resp = reqwest::get(&audio_url).await?;
-                    let audio_data = resp.bytes().await?.to_vec();
-                    sentence.audio_data = Some(audio_data.clone());
-                    audio_data
+        // only one clip plays at a time
+        self.tasks.cancel(TaskKind::Audio, None);
+
+        let cache = self.cache.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+        let dict_word = sentence.parent_expression.dict_word.clone();
+
+        self.tasks
+            .spawn_with_stop(TaskKind::Audio, None, Some(stop), async move {
+                let loaded = sentence.audio_data.is_none();
+                let audio_data = sentence
+                    .load_audio(&cache)
+                    .await
+                    .map_err(|err| err.to_string());
+
+                let (audio_data, result) = match audio_data {
+                    Ok(audio_data) => {
+                        let bytes = audio_data.clone();
+                        let result =
+                            tokio::task::spawn_blocking(move || play_bytes(bytes, stop_flag))
+                                .await
+                                .map_err(|err| err.to_string())
+                                .and_then(|res| res);
+                        (loaded.then_some(audio_data), result)
+                    }
+                    Err(err) => (None, Err(err)),
                 };
 
-                tokio::task::spawn_blocking(move || {
-                    let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-                    let sink = rodio::Sink::try_new(&stream_handle).unwrap();
-                    let cursor = Cursor::new(audio_data);
-                    let source = rodio::Decoder::new(cursor).unwrap();
-                    sink.append(source);
-                    sink.sleep_until_end();
-                })
-                .await
-                .unwrap();
-            }
-        }
+                TaskResult::Audio {
+                    dict_word,
+                    sentence: sentence.sentence,
+                    audio_data,
+                    result,
+                }
+            });
+
         Ok(())
     }
 }
+
+/// Plays `audio_data` until it ends or `stop` is set.
+fn play_bytes(audio_data: Vec<u8>, stop: Arc<AtomicBool>) -> Result<(), String> {
+    let (_stream, stream_handle) =
+        rodio::OutputStream::try_default().map_err(|err| err.to_string())?;
+    let sink = rodio::Sink::try_new(&stream_handle).map_err(|err| err.to_string())?;
+    let source = rodio::Decoder::new(Cursor::new(audio_data)).map_err(|err| err.to_string())?;
+    sink.append(source);
+
+    while !sink.empty() {
+        if stop.load(Ordering::Relaxed) {
+            sink.stop();
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use crate::anki::ConfigJson;
+use crate::app::*;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, List, ListItem},
+};
+use serde::{Deserialize, Serialize};
+
+pub const CATEGORIES: [&str; 5] = ["anime", "drama", "games", "literature", "news"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterMode {
+    /// Hides sentences that don't match.
+    #[default]
+    Strict,
+    /// Moves matching sentences to the top, keeping the rest.
+    Prefer,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Immersion Kit categories to keep, empty keeps all of them.
+    pub categories: Vec<String>,
+    pub deck_blacklist: Vec<String>,
+    pub mode: FilterMode,
+}
+
+/// A category, or one of its decks, with the number of Immersion Kit results.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Facet {
+    pub category: String,
+    pub deck: Option<String>,
+    pub count: u16,
+}
+
+/// The filters applied to Immersion Kit sentences, changed at runtime with the filter picker.
+/// Sentences from other sources don't have a category and are never filtered.
+#[derive(Clone, Debug, Default)]
+pub struct SentenceFilters {
+    pub categories: Vec<String>,
+    /// `priority` in `config.json`.
+    pub deck_whitelist: Vec<String>,
+    pub deck_blacklist: Vec<String>,
+    pub mode: FilterMode,
+}
+
+impl SentenceFilters {
+    pub fn from_config(config: &ConfigJson) -> Self {
+        Self {
+            categories: config.filters.categories.clone(),
+            deck_whitelist: config.priority.clone(),
+            deck_blacklist: config.filters.deck_blacklist.clone(),
+            mode: config.filters.mode,
+        }
+    }
+
+    pub fn matches(&self, sentence: &Sentence) -> bool {
+        let Some(category) = &sentence.category else {
+            return true;
+        };
+        let deck = &sentence.media_title;
+
+        (self.categories.is_empty() || self.categories.contains(category))
+            && (self.deck_whitelist.is_empty() || self.deck_whitelist.contains(deck))
+            && !self.deck_blacklist.contains(deck)
+    }
+
+    pub fn apply(&self, sentences: &[Sentence]) -> Vec<Sentence> {
+        match self.mode {
+            FilterMode::Strict => sentences
+                .iter()
+                .filter(|s| self.matches(s))
+                .cloned()
+                .collect(),
+            FilterMode::Prefer => {
+                let (mut matching, rest): (Vec<Sentence>, Vec<Sentence>) =
+                    sentences.iter().cloned().partition(|s| self.matches(s));
+                matching.extend(rest);
+                matching
+            }
+        }
+    }
+
+    fn toggle(list: &mut Vec<String>, item: &str) {
+        match list.iter().position(|i| i == item) {
+            Some(pos) => {
+                list.remove(pos);
+            }
+            None => list.push(item.to_string()),
+        }
+    }
+
+    /// Cycles a deck between neutral, whitelisted & blacklisted.
+    fn cycle_deck(&mut self, deck: &str) {
+        let deck = deck.to_string();
+        if self.deck_whitelist.contains(&deck) {
+            self.deck_whitelist.retain(|d| *d != deck);
+            self.deck_blacklist.push(deck);
+        } else if self.deck_blacklist.contains(&deck) {
+            self.deck_blacklist.retain(|d| *d != deck);
+        } else {
+            self.deck_whitelist.push(deck);
+        }
+    }
+}
+
+impl Expression {
+    /// Re-filters the fetched sentences, keeping the selection in bounds.
+    pub fn apply_filters(&mut self, filters: &SentenceFilters) {
+        if self.sentences.is_none() {
+            return;
+        }
+        let sentences = filters.apply(&self.unfiltered_sentences);
+        if self.selected_sentence.is_some_and(|i| i >= sentences.len()) {
+            self.selected_sentence = Some(0);
+            self.sentences_state.select(Some(0));
+        }
+        self.sentences = Some(sentences);
+    }
+
+    /// Number of fetched sentences hidden by the filters.
+    pub fn hidden_sentences(&self) -> usize {
+        let shown = self.sentences.as_ref().map_or(0, |s| s.len());
+        self.unfiltered_sentences.len().saturating_sub(shown)
+    }
+}
+
+/// A row in the filter picker.
+enum PickerItem {
+    Mode,
+    Category(String, u16),
+    Deck(String, u16),
+}
+
+impl AppState {
+    /// Re-ranks & re-filters every expression's fetched sentences.
+    pub fn refilter_sentences(&mut self) {
+        for exp in self.expressions.iter_mut() {
+            exp.rank_sentences(&self.known_words);
+            exp.apply_filters(&self.filters);
+        }
+    }
+
+    /// The picker rows, built from the selected expression's Immersion Kit counts.
+    fn picker_items(&self) -> Vec<PickerItem> {
+        let facets = self
+            .selected_expression
+            .and_then(|i| self.expressions.get(i))
+            .map(|exp| exp.facets.as_slice())
+            .unwrap_or_default();
+
+        let mut items = vec![PickerItem::Mode];
+        for category in CATEGORIES {
+            let count = facets
+                .iter()
+                .find(|f| f.category == category && f.deck.is_none())
+                .map_or(0, |f| f.count);
+            items.push(PickerItem::Category(category.to_string(), count));
+
+            items.extend(facets.iter().filter_map(|f| match &f.deck {
+                Some(deck) if f.category == category => {
+                    Some(PickerItem::Deck(deck.clone(), f.count))
+                }
+                _ => None,
+            }));
+        }
+        items
+    }
+
+    pub fn open_filter_picker(&mut self) {
+        if self.filters_state.selected().is_none() {
+            self.filters_state.select(Some(0));
+        }
+        self.selected_page = Pages::Filters;
+    }
+
+    pub fn handle_filter_keybinds(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let items = self.picker_items();
+        let selected = self
+            .filters_state
+            .selected()
+            .unwrap_or(0)
+            .min(items.len() - 1);
+
+        match key.code {
+            KeyCode::Up => {
+                let i = selected.checked_sub(1).unwrap_or(items.len() - 1);
+                self.filters_state.select(Some(i));
+            }
+            KeyCode::Down => {
+                let i = if selected + 1 >= items.len() {
+                    0
+                } else {
+                    selected + 1
+                };
+                self.filters_state.select(Some(i));
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                match &items[selected] {
+                    PickerItem::Mode => {
+                        self.filters.mode = match self.filters.mode {
+                            FilterMode::Strict => FilterMode::Prefer,
+                            FilterMode::Prefer => FilterMode::Strict,
+                        }
+                    }
+                    PickerItem::Category(category, _) => {
+                        SentenceFilters::toggle(&mut self.filters.categories, category)
+                    }
+                    PickerItem::Deck(deck, _) => self.filters.cycle_deck(deck),
+                }
+                self.refilter_sentences();
+            }
+            KeyCode::Char('C') => {
+                self.filters.categories.clear();
+                self.filters.deck_whitelist.clear();
+                self.filters.deck_blacklist.clear();
+                self.refilter_sentences();
+            }
+            KeyCode::Esc => self.selected_page = Pages::Main,
+            _ => {}
+        }
+    }
+
+    pub fn rend_filter_picker(&mut self, area: Rect, buf: &mut Buffer) {
+        let filters = &self.filters;
+        let items: Vec<ListItem> = self
+            .picker_items()
+            .into_iter()
+            .map(|item| {
+                let line = match item {
+                    PickerItem::Mode => Line::from(vec![
+                        Span::styled("Mode: ", Style::default().yellow()),
+                        Span::styled(format!("{:?}", filters.mode), Style::default().white()),
+                    ]),
+                    PickerItem::Category(category, count) => {
+                        let mark = if filters.categories.contains(&category) {
+                            "[x] "
+                        } else {
+                            "[ ] "
+                        };
+                        Line::from(vec![
+                            Span::styled(mark, Color::Green),
+                            Span::styled(category, Style::default().yellow()),
+                            Span::styled(format!(" ({})", count), Color::White),
+                        ])
+                    }
+                    PickerItem::Deck(deck, count) => {
+                        let mark = if filters.deck_whitelist.contains(&deck) {
+                            Span::styled("    [+] ", Color::Green)
+                        } else if filters.deck_blacklist.contains(&deck) {
+                            Span::styled("    [-] ", Color::Red)
+                        } else {
+                            Span::styled("    [ ] ", Color::White)
+                        };
+                        Line::from(vec![
+                            mark,
+                            Span::styled(deck, Color::White),
+                            Span::styled(format!(" ({})", count), Style::default().dim()),
+                        ])
+                    }
+                };
+                ListItem::new(line)
+            })
+            .collect();
+
+        let title = Line::from(vec![
+            Span::styled("Filters ", Style::default().yellow()),
+            Span::styled(
+                "<Enter> Toggle <C> Clear <Esc> Go Back",
+                Style::default().white(),
+            ),
+        ]);
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .style(Style::default().green()),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED).dim());
+
+        StatefulWidget::render(list, area, buf, &mut self.filters_state);
+    }
+}
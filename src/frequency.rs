@@ -0,0 +1,111 @@
+use crate::app::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdMode {
+    /// Shows the rank in red.
+    #[default]
+    Flag,
+    /// Leaves the expression out of the list while it's above the threshold, `words.txt` is left untouched.
+    Hide,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FrequencyConfig {
+    /// Sorts the expressions by frequency rank once the dictionaries are loaded.
+    pub sort: bool,
+    /// Expressions ranked above this are flagged or hidden, 0 disables it.
+    pub threshold: u64,
+    pub threshold_mode: ThresholdMode,
+}
+
+impl Default for FrequencyConfig {
+    fn default() -> Self {
+        Self {
+            sort: true,
+            threshold: 0,
+            threshold_mode: ThresholdMode::Flag,
+        }
+    }
+}
+
+impl FrequencyConfig {
+    pub fn is_above_threshold(&self, rank: Option<u64>) -> bool {
+        self.threshold > 0 && rank.is_some_and(|rank| rank > self.threshold)
+    }
+
+    pub fn is_hidden(&self, rank: Option<u64>) -> bool {
+        self.threshold_mode == ThresholdMode::Hide && self.is_above_threshold(rank)
+    }
+}
+
+impl Expression {
+    /// The rank from the first frequency dictionary that has the expression.
+    pub fn frequency_rank(&self) -> Option<u64> {
+        self.frequencies.first().map(|freq| freq.value)
+    }
+}
+
+impl AppState {
+    /// Indexes of the expressions that aren't hidden by `config.frequency`, in list order.
+    pub fn visible_expressions(&self) -> Vec<usize> {
+        let config = &self.config.frequency;
+        self.expressions
+            .iter()
+            .enumerate()
+            .filter(|(_, exp)| !config.is_hidden(exp.frequency_rank()))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Sorts the expressions if `sort` is set & moves the selection off a hidden expression,
+    /// keeping the selected expression selected otherwise.
+    pub fn apply_frequency_options(&mut self, sort: bool) {
+        let config = self.config.frequency.clone();
+        let selected_word = self
+            .selected_expression
+            .and_then(|i| self.expressions.get(i))
+            .filter(|exp| !config.is_hidden(exp.frequency_rank()))
+            .map(|exp| exp.dict_word.clone());
+
+        if sort {
+            self.sort_expressions_by_frequency();
+        }
+
+        let visible = self.visible_expressions();
+        let hidden = self.expressions.len() - visible.len();
+        if hidden > 0 {
+            self.info.msg = Some(format!(
+                "Hiding {} Expression(s) Ranked Above {}",
+                hidden, config.threshold
+            ));
+        }
+
+        let i = selected_word
+            .and_then(|word| self.expressions.iter().position(|e| e.dict_word == word))
+            .or(visible.first().copied());
+        self.selected_expression = i;
+        self.expressions_state.select(i);
+    }
+
+    /// Where `push_expression` puts an expression: after the ones ranked the same or higher
+    /// when `config.frequency.sort` is set, at the end otherwise.
+    pub fn insert_position(&self, exp: &Expression) -> usize {
+        let rank = exp.frequency_rank().unwrap_or(u64::MAX);
+        if !self.config.frequency.sort || rank == u64::MAX {
+            return self.expressions.len();
+        }
+        self.expressions
+            .iter()
+            .rposition(|e| e.frequency_rank().unwrap_or(u64::MAX) <= rank)
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Most frequent first, expressions without a rank keep their order at the end.
+    pub fn sort_expressions_by_frequency(&mut self) {
+        self.expressions
+            .sort_by_key(|exp| exp.frequency_rank().unwrap_or(u64::MAX));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::app::*;
+use crate::tokenizer::Token;
+use ratatui::text::Span;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Pads the ruby lines, the detail pane's word wrap would trim normal spaces.
+const NBSP: char = '\u{a0}';
+
+/// A run of text, with its reading if it has kanji.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub reading: Option<String>,
+}
+
+impl Segment {
+    fn plain(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            reading: None,
+        }
+    }
+}
+
+fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' | '々' | 'ヶ')
+}
+
+pub fn to_hiragana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Splits `surface` into kanji & kana runs & gives each kanji run its part of `reading`,
+/// ie. 食べる & たべる become 食[た] & べる.
+pub fn align(surface: &str, reading: &str) -> Vec<Segment> {
+    let reading = to_hiragana(reading);
+    if !surface.chars().any(is_kanji) {
+        return vec![Segment::plain(surface)];
+    }
+    if reading.is_empty() || reading == surface {
+        return vec![Segment::plain(surface)];
+    }
+
+    let mut runs: Vec<(String, bool)> = Vec::new();
+    for c in surface.chars() {
+        match runs.last_mut() {
+            Some((run, kanji)) if *kanji == is_kanji(c) => run.push(c),
+            _ => runs.push((c.to_string(), is_kanji(c))),
+        }
+    }
+
+    // kana runs have to match the reading as is, kanji runs take what's between them
+    let pattern: String = runs
+        .iter()
+        .map(|(run, kanji)| {
+            if *kanji {
+                "(.+?)".to_string()
+            } else {
+                format!("({})", regex::escape(&to_hiragana(run)))
+            }
+        })
+        .collect();
+    let whole = vec![Segment {
+        text: surface.to_string(),
+        reading: Some(reading.clone()),
+    }];
+    let Ok(re) = Regex::new(&format!("^{}$", pattern)) else {
+        return whole;
+    };
+    let Some(cap) = re.captures(&reading) else {
+        return whole;
+    };
+
+    runs.iter()
+        .enumerate()
+        .map(|(i, (run, kanji))| Segment {
+            text: run.clone(),
+            reading: kanji.then(|| cap[i + 1].to_string()),
+        })
+        .collect()
+}
+
+/// Segments for the whole sentence, from the tokenizer's readings.
+pub fn from_tokens(sentence: &str, tokens: &[Token]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut last_end = 0;
+    for token in tokens {
+        if token.start > last_end {
+            segments.push(Segment::plain(&sentence[last_end..token.start]));
+        }
+        segments.extend(align(&token.surface, &token.reading));
+        last_end = token.end;
+    }
+    if last_end < sentence.len() {
+        segments.push(Segment::plain(&sentence[last_end..]));
+    }
+    segments
+}
+
+/// Anki's `漢字[かんじ]` markup, with a space before each word that has a reading.
+pub fn to_anki(segments: &[Segment]) -> String {
+    let mut text = String::new();
+    for segment in segments {
+        match &segment.reading {
+            Some(reading) => {
+                if !text.is_empty() {
+                    text.push(' ');
+                }
+                text.push_str(&format!("{}[{}]", segment.text, reading));
+            }
+            None => text.push_str(&segment.text),
+        }
+    }
+    text
+}
+
+/// Parses Anki's furigana markup, where the reading belongs to the text since the last space.
+pub fn parse_anki(furigana: &str) -> Vec<Segment> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r" ?([^ \[\]]+)\[([^\]]*)\]").unwrap());
+    let mut segments = Vec::new();
+    let mut last_end = 0;
+    for cap in re.captures_iter(furigana) {
+        let m = cap.get(0).unwrap();
+        if m.start() > last_end {
+            segments.push(Segment::plain(&furigana[last_end..m.start()]));
+        }
+        // splits off okurigana written inside the base, ie. `食べる[たべる]`
+        segments.extend(align(&cap[1], &cap[2]));
+        last_end = m.end();
+    }
+    if last_end < furigana.len() {
+        segments.push(Segment::plain(&furigana[last_end..]));
+    }
+    segments
+}
+
+/// The readings line & the text line under it, broken so neither is wider than `max_width`.
+pub fn ruby_lines(segments: &[Segment], max_width: usize) -> Vec<(String, String)> {
+    let mut lines = Vec::new();
+    let (mut top, mut bottom, mut width) = (String::new(), String::new(), 0);
+
+    for segment in segments {
+        let reading = segment.reading.as_deref().unwrap_or_default();
+        // text without a reading can be broken up anywhere
+        let pieces: Vec<String> = match segment.reading {
+            Some(_) => vec![segment.text.clone()],
+            None => segment.text.chars().map(|c| c.to_string()).collect(),
+        };
+        for piece in pieces {
+            let text_width = Span::raw(piece.as_str()).width();
+            let reading_width = Span::raw(reading).width();
+            let column = text_width.max(reading_width);
+            if width > 0 && width + column > max_width {
+                lines.push((std::mem::take(&mut top), std::mem::take(&mut bottom)));
+                width = 0;
+            }
+
+            top.push_str(reading);
+            top.extend(std::iter::repeat_n(NBSP, column - reading_width));
+            bottom.push_str(&piece);
+            bottom.extend(std::iter::repeat_n(NBSP, column - text_width));
+            width += column;
+        }
+    }
+    if width > 0 {
+        lines.push((top, bottom));
+    }
+    lines
+}
+
+impl Sentence {
+    /// Furigana from the tokenizer's readings, kept if the source already had some.
+    pub fn furigana_from_tokens(&mut self) {
+        if self.furigana.is_some() || self.tokens.is_empty() {
+            return;
+        }
+        let segments = from_tokens(&self.sentence, &self.tokens);
+        if segments.iter().any(|s| s.reading.is_some()) {
+            self.furigana = Some(to_anki(&segments));
+        }
+    }
+
+    /// Without a tokenizer, only the expression gets furigana, from its first reading.
+    pub fn furigana_from_reading(&mut self, reading: &str) {
+        if self.furigana.is_some() {
+            return;
+        }
+        let Some((start, end)) = self.highlight else {
+            return;
+        };
+
+        // the dictionary form's kanji, with the inflected ending left as is
+        let dict_segments = align(&self.parent_expression.dict_word, reading);
+        let Some(last_kanji) = dict_segments.iter().rposition(|s| s.reading.is_some()) else {
+            return;
+        };
+        let stem: String = dict_segments[..=last_kanji]
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect();
+        let found = &self.sentence[start..end];
+        let Some(ending) = found.strip_prefix(stem.as_str()) else {
+            return;
+        };
+
+        let mut segments = vec![Segment::plain(&self.sentence[..start])];
+        segments.extend_from_slice(&dict_segments[..=last_kanji]);
+        segments.push(Segment::plain(ending));
+        segments.push(Segment::plain(&self.sentence[end..]));
+        self.furigana = Some(to_anki(&segments));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::anki::undo_batch;
+use crate::app::*;
+use crate::cmds::write_to_errs_log;
+use crate::journal::{mark_undone, read_journal, rewritten_notes, JournalBatch};
+use crate::tasks::{TaskKind, TaskResult};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, List, ListItem, Paragraph, Wrap},
+};
+
+impl AppState {
+    /// Opens the journal's batches, the newest first.
+    pub fn open_update_history(&mut self) {
+        match read_journal(&self.config.journal) {
+            Ok(mut batches) => {
+                batches.reverse();
+                let selected = self
+                    .history_state
+                    .selected()
+                    .filter(|i| *i < batches.len())
+                    .or((!batches.is_empty()).then_some(0));
+                self.history_state.select(selected);
+                self.update_history = batches;
+                self.selected_page = Pages::History;
+            }
+            Err(err) => self.update_error_msg("Err Reading Undo Journal", err.to_string()),
+        }
+    }
+
+    /// Restores the newest batch that isn't undone yet.
+    pub fn undo_last_update(&mut self) {
+        let batches = match read_journal(&self.config.journal) {
+            Ok(batches) => batches,
+            Err(err) => {
+                self.update_error_msg("Err Reading Undo Journal", err.to_string());
+                return;
+            }
+        };
+        match batches.into_iter().rev().find(|batch| !batch.undone) {
+            Some(batch) => self.undo_update(batch),
+            None => self.info.msg = Some("Nothing to Undo".to_string()),
+        }
+    }
+
+    fn undo_update(&mut self, batch: JournalBatch) {
+        if self.tasks.is_running(TaskKind::UpdateCards) {
+            self.update_error_msg("Err Undoing Update", "An update is already running".into());
+            return;
+        }
+        if batch.undone {
+            self.update_error_msg("Err Undoing Update", "It was already undone".into());
+            return;
+        }
+        let batches = match read_journal(&self.config.journal) {
+            Ok(batches) => batches,
+            Err(err) => {
+                self.update_error_msg("Err Reading Undo Journal", err.to_string());
+                return;
+            }
+        };
+        let rewritten = rewritten_notes(&batches, &batch);
+        if !rewritten.is_empty() {
+            let err = format!(
+                "{} Note(s) were updated again later, undo the newer update first: {:?}",
+                rewritten.len(),
+                rewritten
+            );
+            self.update_error_msg("Err Undoing Update", err);
+            return;
+        }
+
+        let config = self.config.clone();
+        let len = batch.notes.len() + batch.created.len();
+        self.info.msg = Some(format!("Undoing {} Note(s)", len));
+        self.tasks.spawn(TaskKind::UpdateCards, None, async move {
+            let results = undo_batch(&batch, &config).await;
+            TaskResult::Undo { batch, results }
+        });
+    }
+
+    /// Only marks the batch as undone if every note was restored, so the rest can be retried.
+    pub fn finish_undo(&mut self, batch: JournalBatch, results: Vec<Result<(), String>>) {
+        let restored = batch
+            .notes
+            .iter()
+            .map(|note| format!("Undo {} ({})", note.dict_word, note.id));
+        let deleted = batch
+            .created
+            .iter()
+            .map(|id| format!("Delete added note ({})", id));
+        let log: Vec<String> = restored
+            .chain(deleted)
+            .zip(&results)
+            .filter_map(|(note, res)| Some(format!("{}: {}", note, res.as_ref().err()?)))
+            .collect();
+
+        if log.is_empty() {
+            if let Err(err) = mark_undone(&self.config.journal, batch.id) {
+                self.update_error_msg("Err Writing Undo Journal", err.to_string());
+            }
+        } else {
+            if let Err(err) = write_to_errs_log(&log) {
+                self.update_error_msg("Err Writing to Log:", err.to_string());
+            }
+            let err = format!("{} Note(s) weren't restored: {}", log.len(), log[0]);
+            self.update_error_msg("Err Undoing Update", err);
+        }
+
+        self.info.msg = Some(format!(
+            "Restored {}/{} Note(s)",
+            results.len() - log.len(),
+            results.len()
+        ));
+        if self.selected_page == Pages::History {
+            self.open_update_history();
+        }
+    }
+
+    pub fn handle_history_keybinds(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let len = self.update_history.len();
+        let selected = self.history_state.selected().unwrap_or(0);
+
+        match key.code {
+            KeyCode::Up if len > 0 => {
+                let i = selected.checked_sub(1).unwrap_or(len - 1);
+                self.history_state.select(Some(i));
+            }
+            KeyCode::Down if len > 0 => {
+                let i = if selected + 1 >= len { 0 } else { selected + 1 };
+                self.history_state.select(Some(i));
+            }
+            KeyCode::Char('Z') => {
+                if let Some(batch) = self.update_history.get(selected).cloned() {
+                    self.undo_update(batch);
+                }
+            }
+            KeyCode::Esc => self.selected_page = Pages::Main,
+            _ => {}
+        }
+    }
+
+    pub fn rend_update_history(&mut self, area: Rect, buf: &mut Buffer) {
+        let horizontal =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]);
+        let [list_area, notes_area] = horizontal.areas(area);
+
+        let items: Vec<ListItem> = self
+            .update_history
+            .iter()
+            .map(|batch| {
+                let words: Vec<&str> = batch
+                    .notes
+                    .iter()
+                    .map(|note| note.dict_word.as_str())
+                    .collect();
+                let mut head = vec![
+                    Span::styled(format!("{} ", batch.age()), Style::default().yellow()),
+                    Span::raw(format!("{} Note(s)", batch.notes.len())),
+                ];
+                if !batch.created.is_empty() {
+                    head.push(Span::raw(format!(", {} Added", batch.created.len())));
+                }
+                if batch.undone {
+                    head.push(Span::styled(" undone", Style::default().dim()));
+                }
+                ListItem::new(vec![
+                    Line::from(head),
+                    Line::from(Span::styled(
+                        format!("  {}", words.join("、")),
+                        Style::default().dim(),
+                    )),
+                ])
+            })
+            .collect();
+
+        let title = Line::from(vec![
+            Span::styled("Update History ", Style::default().yellow()),
+            Span::styled("<Z> Undo Update <Esc> Go Back", Style::default().white()),
+        ]);
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .style(Style::default().green()),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED).dim());
+
+        let mut lines: Vec<Line> = Vec::new();
+        let selected = self.history_state.selected();
+        if let Some(batch) = selected.and_then(|i| self.update_history.get(i)) {
+            for note in &batch.notes {
+                lines.push(Line::styled(
+                    format!("{} ({})", note.dict_word, note.id),
+                    Style::default().yellow().bold(),
+                ));
+                let mut fields: Vec<(&String, &String)> = note.fields.iter().collect();
+                fields.sort();
+                for (field, value) in fields {
+                    lines.push(Line::from(vec![
+                        Span::raw(format!("  {}: ", field)),
+                        Span::styled(value.clone(), Style::default().dim()),
+                    ]));
+                }
+            }
+            for id in &batch.created {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("Added ({})", id), Style::default().yellow().bold()),
+                    Span::styled(" deleted by the undo", Style::default().dim()),
+                ]));
+            }
+        }
+        let notes = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::bordered()
+                .title("Fields Before the Update")
+                .style(Style::default().white()),
+        );
+
+        StatefulWidget::render(list, list_area, buf, &mut self.history_state);
+        Widget::render(notes, notes_area, buf);
+    }
+}
@@ -62,14 +62,28 @@ impl AppState {
     }
 
     pub async fn confirm_search_query(&mut self) {
-        let user_input = self.input.text.trim().to_lowercase();
+        let mut user_input = self.input.text.trim().to_lowercase();
         if user_input.is_empty() {
             return;
         }
+        if let Some(form) = self.dictionary_form(&user_input) {
+            self.info.msg = Some(format!(
+                "{} → {} ({})",
+                user_input,
+                form.term,
+                form.reasons.join(", ")
+            ));
+            user_input = form.term;
+        }
 
         if self.input.mode == InputMode::Rename {
             if let Some(i) = self.expressions_state.selected() {
-                self.expressions[i].dict_word.clone_from(&user_input);
+                let mut exp = std::mem::take(&mut self.expressions[i]);
+                exp.dict_word.clone_from(&user_input);
+                exp.readings.clear();
+                exp.definitions.clear();
+                self.apply_dictionaries(&mut exp);
+                self.expressions[i] = exp;
                 self.reset_input();
                 self.input.mode = InputMode::Normal;
                 self.select_mode = SelectMode::Expressions;
@@ -112,9 +126,7 @@ impl AppState {
 
             self.input.mode = InputMode::Search;
             self.select_mode = SelectMode::Expressions;
-            self.expressions
-                .push(Expression::from(user_input.clone(), None, None, None));
-            let i = self.expressions.len() - 1;
+            let i = self.push_expression(Expression::from(user_input.clone(), None, None, None));
             self.expressions_state.select(Some(i));
             self.selected_expression = Some(i);
             self.reset_input();
@@ -0,0 +1,315 @@
+use crate::app::*;
+use crate::tasks::{TaskKind, TaskResult};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::Path;
+use std::sync::Arc;
+
+pub type DictError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct JmdictConfig {
+    pub enabled: bool,
+    /// `JMdict_e` xml, or a jmdict-simplified `.json` file.
+    pub path: String,
+    pub index_path: String,
+}
+
+impl Default for JmdictConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: "data/jmdict/JMdict_e".to_string(),
+            index_path: "data/jmdict/index.json".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DictSense {
+    pub pos: Vec<String>,
+    pub glosses: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DictEntry {
+    pub kanji: Vec<String>,
+    pub readings: Vec<String>,
+    pub senses: Vec<DictSense>,
+}
+
+/// JMdict entries, looked up by any of their kanji or kana forms.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    entries: Vec<DictEntry>,
+    lookup: HashMap<String, Vec<usize>>,
+}
+
+impl Dictionary {
+    pub fn new(entries: Vec<DictEntry>) -> Self {
+        let mut lookup: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            for form in entry.kanji.iter().chain(entry.readings.iter()) {
+                let ids = lookup.entry(form.clone()).or_default();
+                if !ids.contains(&i) {
+                    ids.push(i);
+                }
+            }
+        }
+        Self { entries, lookup }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn lookup(&self, word: &str) -> Vec<&DictEntry> {
+        self.lookup
+            .get(word.trim())
+            .map(|ids| ids.iter().map(|&i| &self.entries[i]).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl DictSense {
+    /// `(n, vs) gloss; gloss`
+    pub fn to_definition(&self) -> String {
+        if self.pos.is_empty() {
+            return self.glosses.join("; ");
+        }
+        format!("({}) {}", self.pos.join(", "), self.glosses.join("; "))
+    }
+}
+
+impl Expression {
+    /// Fills in readings & definitions from the dictionary, keeping any that are already set.
+    pub fn apply_dictionary(&mut self, dict: &Dictionary) {
+        let entries = dict.lookup(&self.dict_word);
+        if entries.is_empty() {
+            return;
+        }
+
+        for reading in entries.iter().flat_map(|e| e.readings.iter()) {
+            if *reading != self.dict_word && !self.readings.contains(reading) {
+                self.readings.push(reading.clone());
+            }
+        }
+        if self.definitions.is_empty() {
+            self.definitions = entries
+                .iter()
+                .flat_map(|e| e.senses.iter())
+                .map(|sense| sense.to_definition())
+                .collect();
+        }
+    }
+}
+
+impl AppState {
+    /// Adds an expression to the list, filled in from the dictionaries that are loaded.
+    /// Returns where it was inserted, see `insert_position`.
+    pub fn push_expression(&mut self, mut exp: Expression) -> usize {
+        self.apply_dictionaries(&mut exp);
+        let i = self.insert_position(&exp);
+        self.expressions.insert(i, exp);
+
+        if let Some(selected) = self.selected_expression.filter(|&selected| selected >= i) {
+            self.selected_expression = Some(selected + 1);
+            self.expressions_state.select(Some(selected + 1));
+        }
+        i
+    }
+
+    pub fn apply_dictionaries(&self, exp: &mut Expression) {
+        if let Some(dict) = &self.dictionary {
+            exp.apply_dictionary(dict);
+        }
+        if let Some(store) = &self.yomitan {
+            exp.apply_yomitan(store);
+        }
+    }
+
+    pub fn spawn_load_dictionary(&mut self) {
+        if !self.config.jmdict.enabled {
+            return;
+        }
+        let config = self.config.jmdict.clone();
+
+        self.tasks.spawn(TaskKind::Dictionary, None, async move {
+            let result = tokio::task::spawn_blocking(move || load_or_build_index(&config))
+                .await
+                .map_err(|err| err.to_string())
+                .and_then(|res| res.map_err(|err| err.to_string()));
+            TaskResult::Dictionary { result }
+        });
+    }
+
+    pub fn set_dictionary(&mut self, dict: Option<Dictionary>) {
+        let Some(dict) = dict else {
+            return;
+        };
+        for exp in self.expressions.iter_mut() {
+            exp.apply_dictionary(&dict);
+        }
+        self.info.msg = Some(format!("Loaded {} JMdict Entries", dict.len()));
+        self.dictionary = Some(Arc::new(dict));
+    }
+}
+
+/// Reads the index from `index_path`, building it from `path` first if it doesn't exist yet
+/// or `path` is newer. Returns `None` if neither file exists.
+pub fn load_or_build_index(config: &JmdictConfig) -> Result<Option<Dictionary>, DictError> {
+    let index_path = Path::new(&config.index_path);
+    let path = Path::new(&config.path);
+
+    let is_fresh = match (index_path.metadata(), path.metadata()) {
+        (Ok(index), Ok(dict)) => index.modified()? >= dict.modified()?,
+        (Ok(_), Err(_)) => true,
+        (Err(_), Ok(_)) => false,
+        (Err(_), Err(_)) => return Ok(None),
+    };
+    if is_fresh {
+        let reader = BufReader::new(File::open(index_path)?);
+        let entries: Vec<DictEntry> = serde_json::from_reader(reader)?;
+        return Ok(Some(Dictionary::new(entries)));
+    }
+
+    let entries = if path.extension().is_some_and(|ext| ext == "json") {
+        parse_simplified_json(path)?
+    } else {
+        parse_xml(path)?
+    };
+
+    if let Some(parent) = index_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let writer = BufWriter::new(File::create(index_path)?);
+    serde_json::to_writer(writer, &entries)?;
+
+    Ok(Some(Dictionary::new(entries)))
+}
+
+/// Parses the `JMdict_e` xml. Part of speech entities (ie. `&adj-na;`) are kept as their name.
+pub fn parse_xml(path: &Path) -> Result<Vec<DictEntry>, DictError> {
+    let element_re = Regex::new(r"^<(keb|reb|pos|gloss)(\s[^>]*)?>(.*)</(keb|reb|pos|gloss)>$")?;
+
+    let mut entries = Vec::new();
+    let mut entry = DictEntry::default();
+    let mut sense = DictSense::default();
+    // a sense without `pos` uses the previous sense's
+    let mut last_pos: Vec<String> = Vec::new();
+
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+
+        match line {
+            "<entry>" => {
+                entry = DictEntry::default();
+                last_pos.clear();
+            }
+            "</entry>" => entries.push(std::mem::take(&mut entry)),
+            "<sense>" => sense = DictSense::default(),
+            "</sense>" => {
+                if sense.pos.is_empty() {
+                    sense.pos.clone_from(&last_pos);
+                } else {
+                    last_pos.clone_from(&sense.pos);
+                }
+                entry.senses.push(std::mem::take(&mut sense));
+            }
+            _ => {
+                let Some(cap) = element_re.captures(line) else {
+                    continue;
+                };
+                let attrs = cap.get(2).map_or("", |m| m.as_str());
+                let text = &cap[3];
+                match &cap[1] {
+                    "keb" => entry.kanji.push(decode_entities(text)),
+                    "reb" => entry.readings.push(decode_entities(text)),
+                    "pos" => sense.pos.push(
+                        text.trim_start_matches('&')
+                            .trim_end_matches(';')
+                            .to_string(),
+                    ),
+                    "gloss" if !attrs.contains("xml:lang") || attrs.contains("\"eng\"") => {
+                        sense.glosses.push(decode_entities(text))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[derive(Deserialize)]
+struct SimplifiedText {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct SimplifiedGloss {
+    lang: String,
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimplifiedSense {
+    part_of_speech: Vec<String>,
+    gloss: Vec<SimplifiedGloss>,
+}
+
+#[derive(Deserialize)]
+struct SimplifiedWord {
+    kanji: Vec<SimplifiedText>,
+    kana: Vec<SimplifiedText>,
+    sense: Vec<SimplifiedSense>,
+}
+
+#[derive(Deserialize)]
+struct SimplifiedJson {
+    words: Vec<SimplifiedWord>,
+}
+
+/// Parses the `jmdict-eng` json from https://github.com/scriptin/jmdict-simplified.
+pub fn parse_simplified_json(path: &Path) -> Result<Vec<DictEntry>, DictError> {
+    let json: SimplifiedJson = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+    let entries = json
+        .words
+        .into_iter()
+        .map(|word| DictEntry {
+            kanji: word.kanji.into_iter().map(|k| k.text).collect(),
+            readings: word.kana.into_iter().map(|k| k.text).collect(),
+            senses: word
+                .sense
+                .into_iter()
+                .map(|sense| DictSense {
+                    pos: sense.part_of_speech,
+                    glosses: sense
+                        .gloss
+                        .into_iter()
+                        .filter(|g| g.lang == "eng")
+                        .map(|g| g.text)
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(entries)
+}
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the fields notes had before an update are kept, so it can be undone.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct JournalConfig {
+    pub enabled: bool,
+    pub path: String,
+    /// Older batches are dropped.
+    pub max_batches: usize,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: "data/update_journal.json".to_string(),
+            max_batches: 50,
+        }
+    }
+}
+
+/// A note's fields before an update wrote to them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NoteSnapshot {
+    pub id: u128,
+    pub dict_word: String,
+    /// Only the fields the update wrote.
+    pub fields: HashMap<String, String>,
+}
+
+/// The notes one update wrote to.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JournalBatch {
+    /// When it was written, in milliseconds since the unix epoch.
+    pub id: u64,
+    pub notes: Vec<NoteSnapshot>,
+    /// Ids of the notes it added, they're deleted when it's undone.
+    #[serde(default)]
+    pub created: Vec<u128>,
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl JournalBatch {
+    pub fn new(notes: Vec<NoteSnapshot>) -> Self {
+        Self {
+            id: now_millis(),
+            notes,
+            created: Vec::new(),
+            undone: false,
+        }
+    }
+
+    /// The notes it wrote to or added.
+    pub fn note_ids(&self) -> impl Iterator<Item = u128> + '_ {
+        let updated = self.notes.iter().map(|note| note.id);
+        updated.chain(self.created.iter().copied())
+    }
+
+    /// How long ago the batch was written, ie. `5m ago`.
+    pub fn age(&self) -> String {
+        let secs = now_millis().saturating_sub(self.id) / 1000;
+        match secs {
+            0..=59 => format!("{}s ago", secs),
+            60..=3599 => format!("{}m ago", secs / 60),
+            3600..=86399 => format!("{}h ago", secs / 3600),
+            _ => format!("{}d ago", secs / 86400),
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+/// Every batch in the journal, the newest last.
+pub fn read_journal(config: &JournalConfig) -> std::io::Result<Vec<JournalBatch>> {
+    let data = match std::fs::read(&config.path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Keeps the newest `max_batches` of `batches`. Written to a temp file that replaces the
+/// journal, so a crash mid-write can't leave it half written.
+fn write_journal(config: &JournalConfig, batches: &[JournalBatch]) -> std::io::Result<()> {
+    if let Some(parent) = Path::new(&config.path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let start = batches.len().saturating_sub(config.max_batches);
+    let tmp_path = format!("{}.tmp", config.path);
+    std::fs::write(&tmp_path, serde_json::to_vec(&batches[start..])?)?;
+    std::fs::rename(&tmp_path, &config.path)
+}
+
+/// serde_json's errors for invalid & truncated json.
+fn is_unparsable(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::InvalidData | ErrorKind::UnexpectedEof
+    )
+}
+
+/// Moves a journal that can't be parsed to `<path>.<millis>.bak`, returning where it went.
+fn backup_journal(config: &JournalConfig) -> std::io::Result<String> {
+    let backup = format!("{}.{}.bak", config.path, now_millis());
+    std::fs::rename(&config.path, &backup)?;
+    Ok(backup)
+}
+
+/// Ids of `batch`'s notes that a later batch, which isn't undone, wrote to again.
+/// Undoing `batch` first would overwrite that later update.
+pub fn rewritten_notes(batches: &[JournalBatch], batch: &JournalBatch) -> Vec<u128> {
+    let later: Vec<&JournalBatch> = batches
+        .iter()
+        .filter(|later| later.id > batch.id && !later.undone)
+        .collect();
+    batch
+        .note_ids()
+        .filter(|id| later.iter().any(|later| later.note_ids().any(|n| n == *id)))
+        .collect()
+}
+
+/// A journal that can't be parsed is backed up & a new one started, so it can't block updates.
+/// Returns a warning for the caller to log when that happens.
+pub fn record_batch(
+    config: &JournalConfig,
+    batch: JournalBatch,
+) -> std::io::Result<Option<String>> {
+    let (mut batches, warning) = match read_journal(config) {
+        Ok(batches) => (batches, None),
+        Err(err) if is_unparsable(&err) => {
+            let backup = backup_journal(config)?;
+            let warning = format!(
+                "Undo journal `{}` was unreadable ({}), moved it to `{}`",
+                config.path, err, backup
+            );
+            (Vec::new(), Some(warning))
+        }
+        Err(err) => return Err(err),
+    };
+    batches.push(batch);
+    write_journal(config, &batches)?;
+    Ok(warning)
+}
+
+/// Adds the notes the update added to its batch, dropping the batch if it's left empty.
+pub fn finish_batch(config: &JournalConfig, id: u64, created: Vec<u128>) -> std::io::Result<()> {
+    let mut batches = read_journal(config)?;
+    if let Some(batch) = batches.iter_mut().find(|batch| batch.id == id) {
+        batch.created = created;
+    }
+    batches.retain(|batch| batch.id != id || !(batch.notes.is_empty() && batch.created.is_empty()));
+    write_journal(config, &batches)
+}
+
+pub fn mark_undone(config: &JournalConfig, id: u64) -> std::io::Result<()> {
+    let mut batches = read_journal(config)?;
+    for batch in batches.iter_mut().filter(|batch| batch.id == id) {
+        batch.undone = true;
+    }
+    write_journal(config, &batches)
+}
@@ -4,11 +4,18 @@ use ratatui::{
     prelude::*,
     widgets::{Block, List, ListItem, ListState, Paragraph},
 };
-use std::{io, time::Instant};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
 
-use crate::anki::{read_config, return_new_anki_words, update_anki_cards, UpdateNotesRes};
+use crate::anki::{
+    plan_note_updates, read_config, return_new_anki_words, update_anki_cards, NoteOutcome,
+    NoteResult, UpdateNotesRes,
+};
 use crate::app::{AppState, InputMode, Pages, SelectMode, Sentence};
 use crate::cmds::write_to_errs_log;
+use crate::tasks::{TaskKind, TaskResult};
 
 #[derive(Default, Debug, PartialEq)]
 pub enum KeybindSections {
@@ -57,8 +64,9 @@ impl AppState {
                                             if self.expressions.contains(&exp) {
                                                 continue;
                                             }
-                                            self.expressions.push(exp);
+                                            self.push_expression(exp);
                                         }
+                                        self.apply_frequency_options(self.config.frequency.sort);
                                     }
                                     Err(e) => {
                                         self.update_error_msg("New Anki Notes Err", e.to_string());
@@ -90,11 +98,10 @@ impl AppState {
                                     return Ok(());
                                 }
                                 if let Some(i) = self.selected_expression {
-                                    self.expressions[i].sentences = None;
                                     self.select_mode = SelectMode::Sentences;
+                                    self.expressions[i].sentences_state.select(Some(0));
                                     if self.expressions[i].sentences.is_none() {
-                                        self.expressions[i].sentences_state.select(Some(0));
-                                        self.fetch_sentences(false).await;
+                                        self.fetch_sentences(false);
                                     }
                                 }
                             }
@@ -107,10 +114,12 @@ impl AppState {
                                     self.select_mode = SelectMode::Sentences;
                                     if self.expressions[i].sentences.is_none() {
                                         self.expressions[i].sentences_state.select(Some(0));
-                                        self.fetch_sentences(true).await;
+                                        self.fetch_sentences(true);
                                     }
                                 }
                             }
+                            KeyCode::Char('X') => self.cancel_selected_tasks(),
+                            KeyCode::Char('O') => self.apply_frequency_options(true),
                             KeyCode::Down => self.select_next_exp(),
                             KeyCode::Up => self.select_prev_exp(),
                             _ => {}
@@ -121,8 +130,9 @@ impl AppState {
                     if !self.handle_global_keybinds(key) {
                         match key.code {
                             KeyCode::Char('L') => self.open_website_link(),
+                            KeyCode::Char('X') => self.cancel_selected_tasks(),
                             KeyCode::Char('P') => {
-                                if let Err(err) = self.play_audio().await {
+                                if let Err(err) = self.play_audio() {
                                     self.update_error_msg("Error Playing Audio", err.to_string());
                                 }
                             }
@@ -167,17 +177,24 @@ impl AppState {
                             return Ok(());
                         }
 
-                        self.handle_update_cards_wrapper().await;
+                        self.handle_update_cards_wrapper();
                     }
                     KeyCode::Esc => self.select_mode = SelectMode::Expressions,
                     KeyCode::Char('I') => self.select_mode = SelectMode::Input,
                     KeyCode::Char('D') => self.delete_note(),
+                    KeyCode::Char('U') => self.selected_page = Pages::Results,
+                    KeyCode::Char('Z') => self.undo_last_update(),
+                    KeyCode::Char('J') => self.open_update_history(),
                     KeyCode::Up => self.select_prev_note(),
                     KeyCode::Down => self.select_next_note(),
                     _ => {}
                 },
                 _ => {}
             },
+            Pages::Filters => self.handle_filter_keybinds(key),
+            Pages::Results => self.handle_results_keybinds(key),
+            Pages::Preview => self.handle_preview_keybinds(key),
+            Pages::History => self.handle_history_keybinds(key),
             Pages::Help => {
                 match self.keybinds.selected_section {
                     KeybindSections::Expressions if key.kind == KeyEventKind::Press => {
@@ -240,8 +257,12 @@ impl AppState {
         Ok(())
     }
 
-    async fn handle_update_cards_wrapper(&mut self) {
-        let instant = Instant::now();
+    fn handle_update_cards_wrapper(&mut self) {
+        if self.tasks.is_running(TaskKind::UpdateCards) {
+            self.update_error_msg("Err Updating Notes", "An update is already running".into());
+            return;
+        }
+
         let config = match read_config() {
             Ok(cfg) => cfg,
             Err(e) => {
@@ -250,45 +271,93 @@ impl AppState {
             }
         };
 
-        let config_clone = config.clone();
         let ntbc_sents = std::mem::take(&mut self.notes_to_be_created.sentences);
+        if config.options.preview_updates {
+            self.info.msg = Some(format!("Previewing {} Note(s)", ntbc_sents.len()));
+            self.tasks.spawn(TaskKind::UpdateCards, None, async move {
+                let plan = plan_note_updates(ntbc_sents, &config).await;
+                TaskResult::PlanUpdate {
+                    plan: Box::new(plan),
+                }
+            });
+            return;
+        }
 
-        let mut res_err: String = String::new();
+        self.info.msg = Some(format!("Updating {} Note(s)", ntbc_sents.len()));
 
-        let res: Option<UpdateNotesRes> = match update_anki_cards(ntbc_sents, &config_clone).await {
-            Ok(res) => Some(res),
-            Err(e) => {
-                res_err = format!("Err Updating Notes: {}", e);
-                None
-            }
-        };
+        self.tasks.spawn(TaskKind::UpdateCards, None, async move {
+            let instant = Instant::now();
+            let res = update_anki_cards(ntbc_sents, &config).await;
 
-        if res.is_none() {
-            self.errors.push(res_err);
-            return;
-        }
+            TaskResult::UpdateCards {
+                elapsed: instant.elapsed(),
+                res,
+            }
+        });
+    }
 
+    pub fn finish_update_cards(&mut self, elapsed: Duration, res: UpdateNotesRes) {
         self.select_mode = SelectMode::Expressions;
 
-        let res = res.unwrap();
+        for result in &res.results {
+            if let NoteOutcome::Created(id) = result.outcome {
+                let dict_word = &result.sentence.parent_expression.dict_word;
+                if let Some(exp) = self
+                    .expressions
+                    .iter_mut()
+                    .find(|e| e.dict_word == *dict_word)
+                {
+                    exp.note_id = Some(id);
+                }
+            }
+        }
 
-        if config.options.del_words {
+        if self.config.options.del_words {
             self.delete_notes_after_update_wrapper(&res);
         }
 
-        // write any errors that happened while creating notes to err_log.txt
-        if let Err(err) = write_to_errs_log(&res.err_vec) {
+        let failed: Vec<&NoteResult> = res
+            .results
+            .iter()
+            .filter(|r| !r.outcome.is_success())
+            .collect();
+        let log: Vec<String> = failed
+            .iter()
+            .map(|r| {
+                let dict_word = &r.sentence.parent_expression.dict_word;
+                format!("{}: {} | {}", dict_word, r.outcome, r.sentence.sentence)
+            })
+            .collect();
+        if let Err(err) = write_to_errs_log(&log) {
             self.update_error_msg("Err Writing to Log:", err.to_string());
         }
 
-        let elapsed = instant.elapsed().as_secs();
+        // failed sentences go back to the notes list for a retry, unless the word got a new one since
+        for r in failed {
+            let mut sentence = r.sentence.clone();
+            if let Some(id) = r.outcome.note_id() {
+                sentence.note_id = Some(id);
+            }
+            let word = &sentence.parent_expression.dict_word;
+            if !self
+                .notes_to_be_created
+                .sentences
+                .iter()
+                .any(|s| s.parent_expression.dict_word == *word)
+            {
+                self.notes_to_be_created.sentences.push(sentence);
+            }
+        }
+
         self.info.msg = Some(format!(
-            "🗸: {} | ✗: {} | total: {} | in {}s.",
-            res.success_len,
-            res.err_vec.len(),
-            res.total_len,
-            elapsed
+            "🗸: {} | new: {} | ✗: {} | total: {} | in {}s.",
+            res.success_len(),
+            res.created_len(),
+            res.results.len() - res.success_len(),
+            res.results.len(),
+            elapsed.as_secs()
         ));
+        self.open_update_results(res.results);
     }
 
     fn handle_global_keybinds(&mut self, key: KeyEvent) -> bool {
@@ -311,6 +380,10 @@ impl AppState {
                 self.selected_page = Pages::Main;
                 true
             }
+            KeyCode::Char('F') => {
+                self.open_filter_picker();
+                true
+            }
             KeyCode::Char('R') if !key.modifiers.contains(KeyModifiers::CONTROL) => {
                 self.restart_program();
                 true
@@ -458,41 +531,40 @@ impl AppState {
     }
 
     pub fn select_prev_exp(&mut self) {
-        if self.expressions.is_empty() {
+        // hidden expressions are skipped
+        let visible = self.visible_expressions();
+        let (Some(&first), Some(&last)) = (visible.first(), visible.last()) else {
             return;
-        }
+        };
         let i = match self.expressions_state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.expressions.len().saturating_sub(1)
-                } else {
-                    i - 1
-                }
-            }
-            None => self.selected_expression.unwrap_or(0),
+            Some(i) => visible
+                .iter()
+                .rev()
+                .find(|&&v| v < i)
+                .copied()
+                .unwrap_or(last),
+            None => self.selected_expression.unwrap_or(first),
         };
 
         self.selected_expression = Some(i);
         self.expressions_state.select(Some(i));
+        self.prefetch_next();
     }
 
     pub fn select_next_exp(&mut self) {
-        if self.expressions.is_empty() {
+        // hidden expressions are skipped
+        let visible = self.visible_expressions();
+        let Some(&first) = visible.first() else {
             return;
-        }
+        };
         let i = match self.expressions_state.selected() {
-            Some(i) => {
-                if i == self.expressions.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => self.selected_expression.unwrap_or(0),
+            Some(i) => visible.iter().find(|&&v| v > i).copied().unwrap_or(first),
+            None => self.selected_expression.unwrap_or(first),
         };
 
         self.selected_expression = Some(i);
         self.expressions_state.select(Some(i));
+        self.prefetch_next();
     }
 
     pub fn rend_main_keybinds(&self, area: Rect, buf: &mut Buffer) {
@@ -500,6 +572,8 @@ impl AppState {
             vec![
                 "<H> ".green(),
                 "Help Page ".into(),
+                "<F> ".yellow(),
+                "Filters ".into(),
                 "<R> ".red(),
                 "Restart Program ".into(),
                 // "<S> ".white(),
@@ -745,19 +819,24 @@ impl Keybinds {
     pub fn new() -> Self {
         // exp
 
-        let exp_titles = ["Enter", "C-Enter", "I", "Y", "D", "E", "C-r", "Up", "Down"]
-            .iter()
-            .map(|kb| kb.to_string())
-            .collect();
+        let exp_titles = [
+            "Enter", "C-Enter", "I", "Y", "D", "E", "C-r", "X", "F", "O", "Up", "Down",
+        ]
+        .iter()
+        .map(|kb| kb.to_string())
+        .collect();
 
         let exp_abouts = [
-                "Fetches Sentences\n‎\nFetches sentences from Immersion Kit, or Massif.la.\n‎\nSentences may include, or exactly match the selected Expression in one of its forms.\nDepending on the word's rarity, either it's kanji form, or it's kana reading may provide more accurate results.\nSee `<C-Enter>` for more information on sentence accuracy.\n‎\nIf no sentences are found from Immersion Kit, it will fetch sentences from Massif.la.\nMassif.la sentences don't contain audio or images.\n(WIP) You can set `\"tts\": true` in your config.json to generate audio for the sentence.",
-                "[Ctrl + Enter] - Enables `Exact Search`\n‎\nThis will find sentences that contain a 1 - 1 exact match of the selected Expression (only) on Immersion Kit.\n‎\nThis means that it will not try to match the Expressions kana reading(s).\nOr if the Expression is a verb, it will not recognize it's conjugated forms.\n‎\nIf no sentences are found from Immersion Kit with `Exact Search` enabled, it will still fetch from Massif.la (with `Exact Search` disabled).",
+                "Fetches Sentences\n‎\nFetches sentences from Immersion Kit, Massif.la, or your local subtitles, novels & Tatoeba.\n‎\nSentences that were already fetched (or prefetched) are shown instantly, `<C-Enter>` refetches them.\nSentences may include, or exactly match the selected Expression in one of its forms.\nDepending on the word's rarity, either it's kanji form, or it's kana reading may provide more accurate results.\n‎\nSources are tried in the order of `\"sources\"` in your config.json, the shipped one starts with your local `\"subtitles\"` (without it: `[\"immersion_kit\", \"massif\"]`).\nIf no sentences are found from one source, it will fetch sentences from the next one.\nMassif.la sentences don't contain audio or images.\n(WIP) You can set `\"tts\": true` in your config.json to generate audio for the sentence.",
+                "[Ctrl + Enter] - Skips Immersion Kit\n‎\nFetches sentences from every source in your `\"sources\"` list except Immersion Kit, so without your own sources it fetches from Massif.la directly.",
                 "Focuses the Search Box\n‎\nPress <I> to see Search Box keybinds.",
                 "Copies Selected Expression into Input Box\n‎\nPress <I> to see Search Box keybinds.",
                 "Deletes the Selected Expression\n‎\nThis will also remove the expression from your words.txt file.\nYou can set `\"del_word\": true` in your config.json to automatically delete selected Expressions from your words.txt after updating their Anki Notes.",
                 "Opens Note GUI\n‎\nOpens Anki's Note Editor GUI for the selected Expression\nNote: Only opens the GUI if the Expression has an ID.",
                 "Edit Expression\n‎\nFocuses the Search Box and changes the selected Expression's text on Enter.\nPress <I> to see Search Box keybinds.",
+                "Cancels Fetching\n‎\nCancels the selected Expression's sentence fetch & stops any playing audio.\nExpressions that are still fetching show a spinner in the Expressions List.",
+                "Opens the Filter Picker\n‎\nFilters Immersion Kit sentences by category & deck, using the selected Expression's result counts.\n‎\n<Enter> toggles a category, or cycles a deck between [+] only show, [-] hide & neutral. <C> clears all filters.\n`Strict` mode hides sentences that don't match, `Prefer` mode only moves matching sentences to the top.\nDefaults are read from `\"filters\"` & `\"priority\"` in your config.json.",
+                "Sorts by Frequency\n‎\nSorts the Expressions List by frequency rank, most frequent first.\nRanks come from the first frequency dictionary in `\"yomitan\"` that has the Expression, and are shown next to each Expression.\n‎\nSet `\"frequency\": {\"sort\": true}` in your config.json to sort automatically once the dictionaries are loaded.\nExpressions ranked above `\"threshold\"` are shown in red, or left out of the list with `\"threshold_mode\": \"hide\"`.",
                 "Selects the Previous Expression\n‎\nFocuses the Previous Expression in the Expressions List.",
                 "Selects the Next Expression\n‎\nFocuses the Next Expression in the Expressions List.",
             ]
@@ -767,13 +846,14 @@ impl Keybinds {
 
         // sent
 
-        let sent_titles = ["P", "L", "Esc", "Up", "Down"]
+        let sent_titles = ["P", "X", "L", "Esc", "Up", "Down"]
             .iter()
             .map(|kb| kb.to_string())
             .collect();
 
         let sent_abouts =[
                 "Plays Audio\n‎\nPlays the Sentence's Audio.\n‎\nMassif.la sentences don't contain audio, so nothing will play.\n(WIP) You can set `\"tts\": true` in your config.json to generate audio for the sentence.",
+                "Stops Audio\n‎\nStops the Sentence's Audio if it is playing.",
                 "Opens Sentence in the Default Browser\n‎\nThis will take you to either Immersion Kit, or Massif.la's website with the sentence pasted into the Search Bar.",
                 "Focuses Expressions List\n‎\nUnfocuses the Sentences List & Focuses the Expressions List.",
                 "Selects the Previous Sentence\n‎\nFocuses the Previous Expression in the Sentences List.",
@@ -785,15 +865,18 @@ impl Keybinds {
 
         // notes
 
-        let note_titles = ["C-Enter", "D", "N", "I", "Esc"]
+        let note_titles = ["C-Enter", "D", "N", "I", "U", "Z", "J", "Esc"]
             .iter()
             .map(|kb| kb.to_string())
             .collect();
 
-        let note_abouts = ["[Ctrl + Enter] - Update Notes\n‎\nFinds, checks, then updates any Anki Notes that contain the selected Expressions.\n‎\nIf the selected Sentence was fetched from Massif.la, it will only update the Sentence field specified in your config.json.\nOtherwise it will update the Sentence, Image, and Audio fields.\nNote: some entries on Immersion Kit may *not contain an image file (ie. -Skyrim).\n‎\nWarning: Will overwrite existing data in the Sentence field if successful.",
+        let note_abouts = ["[Ctrl + Enter] - Update Notes\n‎\nFinds, checks, then updates any Anki Notes that contain the selected Expressions.\n‎\nIf the selected Sentence was fetched from Massif.la, it will only update the Sentence field specified in your config.json.\nOtherwise it will update the Sentence, Image, and Audio fields.\nNote: some entries on Immersion Kit may *not contain an image file (ie. -Skyrim).\n‎\nWarning: Will overwrite existing data in the Sentence field if successful.\nWith `preview_updates` on in config.json's `options`, the changes are shown first, & nothing is written until they're approved.",
             "Deletes the Selected Sentence\n‎\nRemoves the sentence from the Notes list.",
             "Focuses the Notes Section\n‎\nFocuses the Notes section if it is not already focused.",
-            "Focuses Search Box\n‎\nFocuses the Search Box and awaits an Anki Note ID.\n‎\nAnki's search can sometimes be inconsistant, so you can specify the exact ID of the Note you want to update for each selected Sentence.\nSentences that don't have an ID specified will display `| Note ID: ?` in the title, indicating it will try to find a Note based on the Sentence's Expression.\n‎\nAn unspecified ID will never update an Anki Note that does not have an exact match.\nIf any of the selected Expressions fail to find an exact match (or ID), none of them will be updated.",
+            "Focuses Search Box\n‎\nFocuses the Search Box and awaits an Anki Note ID.\n‎\nAnki's search can sometimes be inconsistant, so you can specify the exact ID of the Note you want to update for each selected Sentence.\nSentences that don't have an ID specified will display `| Note ID: ?` in the title, indicating it will try to find a Note based on the Sentence's Expression.\n‎\nAn unspecified ID will never update an Anki Note that does not have an exact match.\nSentences that fail to find an exact match (or ID) aren't updated, the rest still are.",
+            "Opens the Update Results\n‎\nShows what happened to each Sentence of the last update: updated, added, no Note found, more than one Note found, a missing field, or failed media.\nIt opens by itself after every update, & failed Sentences are put back in the Notes list to retry.\nDetails are also written to `data/err_log.txt`.",
+            "Undoes the Last Update\n‎\nBefore an update writes to a Note, the fields it overwrites are saved to the undo journal (`data/update_journal.json`).\nThis writes them back for the newest update that wasn't undone yet, & deletes the Notes it added.",
+            "Opens the Update History\n‎\nShows the updates in the undo journal & the fields each Note had before it.\nPress <Z> to undo the selected update, an older update can only be undone once the newer ones that wrote to the same Notes are.",
             "Focuses to the Expressions Section\n‎\nFocuses the Expressions section if focused on the Notes section.",
             ]
             .iter()
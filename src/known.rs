@@ -0,0 +1,192 @@
+use crate::anki::{expression_text, find_note_ids, notes_info, ConfigJson};
+use crate::app::*;
+use crate::deinflect::deinflect;
+use crate::tasks::{TaskKind, TaskResult};
+use anki_direct::AnkiClient;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Longest word looked up while splitting a sentence.
+const MAX_WORD_LEN: usize = 12;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct KnownWordsConfig {
+    pub enabled: bool,
+    /// Anki search for the notes you already know, empty skips Anki.
+    pub query: String,
+    /// Extra known words, separated by whitespace.
+    pub file: String,
+}
+
+impl Default for KnownWordsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            query: "-is:new prop:ivl>=21".to_string(),
+            file: "data/known_words.txt".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CharKind {
+    Kanji,
+    Hiragana,
+    Katakana,
+    Other,
+}
+
+fn char_kind(c: char) -> CharKind {
+    match c {
+        '\u{3040}'..='\u{309f}' => CharKind::Hiragana,
+        '\u{30a0}'..='\u{30ff}' | '\u{ff66}'..='\u{ff9f}' => CharKind::Katakana,
+        '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' | '々' => CharKind::Kanji,
+        _ => CharKind::Other,
+    }
+}
+
+/// Words that are never counted as unknown: kana-only particles & okurigana, punctuation, latin.
+fn is_countable(word: &str) -> bool {
+    word.chars()
+        .any(|c| matches!(char_kind(c), CharKind::Kanji | CharKind::Katakana))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct KnownWords {
+    words: HashSet<String>,
+}
+
+impl KnownWords {
+    pub fn from_words<I: IntoIterator<Item = String>>(words: I) -> Self {
+        let mut known = HashSet::new();
+        for word in words {
+            let word = word.trim().to_string();
+            if !word.is_empty() {
+                known.insert(word);
+            }
+        }
+        Self { words: known }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Whether `word` is known, or is an inflection of a known word (ie. 食べた of 食べる).
+    pub fn is_known(&self, word: &str) -> bool {
+        self.words.contains(word)
+            || (word.ends_with(|c| char_kind(c) == CharKind::Hiragana)
+                && deinflect(word).iter().any(|d| self.words.contains(&d.term)))
+    }
+
+    /// Splits `text` by the longest known word or inflection at each position,
+    /// anything else is split where the script changes.
+    pub fn segment(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut words = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let longest = (1..=MAX_WORD_LEN.min(chars.len() - i)).rev().find(|&len| {
+                let word: String = chars[i..i + len].iter().collect();
+                self.is_known(&word)
+            });
+
+            let len = longest.unwrap_or_else(|| {
+                let kind = char_kind(chars[i]);
+                chars[i..]
+                    .iter()
+                    .take_while(|&&c| char_kind(c) == kind)
+                    .count()
+            });
+            words.push(chars[i..i + len].iter().collect());
+            i += len;
+        }
+
+        words
+    }
+
+    /// Counts the words in the sentence that aren't known.
+    /// Uses Immersion Kit's word list when there is one.
+    pub fn count_unknown(&self, sentence: &Sentence) -> usize {
+        let words = if sentence.words.is_empty() {
+            self.segment(&sentence.sentence)
+        } else {
+            sentence.words.clone()
+        };
+
+        words
+            .iter()
+            .filter(|word| is_countable(word) && !self.is_known(word))
+            .count()
+    }
+}
+
+impl Expression {
+    /// Scores the fetched sentences & moves the ones closest to i+1 to the top.
+    pub fn rank_sentences(&mut self, known: &KnownWords) {
+        if known.is_empty() {
+            return;
+        }
+        for sentence in self.unfiltered_sentences.iter_mut() {
+            sentence.unknown_words = Some(known.count_unknown(sentence));
+        }
+        self.unfiltered_sentences.sort_by_key(|s| {
+            let unknown = s.unknown_words.unwrap_or(usize::MAX);
+            (unknown.abs_diff(1), unknown)
+        });
+    }
+}
+
+async fn load_known_words(
+    client: AnkiClient,
+    config: ConfigJson,
+) -> Result<KnownWords, Box<dyn std::error::Error + Send + Sync>> {
+    let mut words: Vec<String> = match std::fs::read_to_string(&config.known_words.file) {
+        Ok(text) => text.split_whitespace().map(|w| w.to_string()).collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    if !config.known_words.query.is_empty() {
+        let url = &config.anki_connect.url;
+        let ids = find_note_ids(&client.client, url, &config.known_words.query).await?;
+        for ids in ids.chunks(500) {
+            let infos = notes_info(&client.client, url, ids.to_vec()).await?;
+            words.extend(infos.iter().filter_map(|info| {
+                let field = info.fields.get(&config.fields.expression)?;
+                Some(expression_text(&field.value))
+            }));
+        }
+    }
+
+    Ok(KnownWords::from_words(words))
+}
+
+impl AppState {
+    pub fn spawn_load_known_words(&mut self) {
+        if !self.config.known_words.enabled {
+            return;
+        }
+        let client = self.client.clone();
+        let config = self.config.clone();
+
+        self.tasks.spawn(TaskKind::KnownWords, None, async move {
+            let result = load_known_words(client, config)
+                .await
+                .map_err(|err| err.to_string());
+            TaskResult::KnownWords { result }
+        });
+    }
+
+    pub fn set_known_words(&mut self, known: KnownWords) {
+        self.info.msg = Some(format!("Loaded {} Known Words", known.len()));
+        self.known_words = known;
+        self.refilter_sentences();
+    }
+}
@@ -2,12 +2,31 @@
 
 mod app;
 mod ui;
+mod jmdict;
+mod journal;
 mod keybinds;
+mod known;
 mod cmds;
+mod deinflect;
 mod fetch;
+mod filters;
+mod frequency;
+mod furigana;
+mod history;
 mod anki;
 mod input;
 mod audio;
+mod cache;
+mod pitch;
+mod prefetch;
+mod preview;
+mod results;
+mod sources;
+mod tasks;
+mod tokenizer;
+mod yomitan;
+#[cfg(test)]
+mod tests;
 
 use std::{error::Error, io::stdout};
 use color_eyre::config::HookBuilder;
@@ -0,0 +1,162 @@
+use crate::yomitan::Pitch;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const SMALL_KANA: &str = "ゃゅょぁぃぅぇぉゎャュョァィゥェォヮ";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PitchConfig {
+    /// Kanjium's `accents.txt`, skipped if it doesn't exist.
+    pub kanjium: String,
+}
+
+impl Default for PitchConfig {
+    fn default() -> Self {
+        Self {
+            kanjium: "data/pitch/accents.txt".to_string(),
+        }
+    }
+}
+
+/// Reads Kanjium's tab separated `expression  reading  accents` file,
+/// where accents are comma separated downstep positions.
+pub fn read_kanjium(path: &Path) -> std::io::Result<Vec<Pitch>> {
+    let text = std::fs::read_to_string(path)?;
+
+    let pitches = text
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split('\t');
+            let expression = cols.next()?.trim();
+            let reading = cols.next()?.trim();
+            let positions: Vec<u8> = cols
+                .next()?
+                .split(',')
+                // some accents are prefixed by a part of speech, ie. `(名)0`
+                .filter_map(|p| p.rsplit(')').next()?.trim().parse().ok())
+                .collect();
+            if positions.is_empty() {
+                return None;
+            }
+
+            Some(Pitch {
+                dict: "Kanjium".to_string(),
+                expression: expression.to_string(),
+                reading: if reading.is_empty() {
+                    expression
+                } else {
+                    reading
+                }
+                .to_string(),
+                positions,
+            })
+        })
+        .collect();
+
+    Ok(pitches)
+}
+
+/// Splits kana into morae, small kana belong to the mora before them.
+pub fn morae(reading: &str) -> Vec<String> {
+    let mut morae: Vec<String> = Vec::new();
+    for c in reading.chars() {
+        match morae.last_mut() {
+            Some(last) if SMALL_KANA.contains(c) => last.push(c),
+            _ => morae.push(c.to_string()),
+        }
+    }
+    morae
+}
+
+/// Whether each mora is high for a downstep at `position`.
+pub fn pitch_pattern(mora_count: usize, position: u8) -> Vec<bool> {
+    let position = position as usize;
+    (0..mora_count)
+        .map(|i| match position {
+            // heiban, low then high
+            0 => i > 0,
+            // atamadaka, high then low
+            1 => i == 0,
+            // nakadaka & odaka, low, high until the downstep, then low
+            _ => i > 0 && i < position,
+        })
+        .collect()
+}
+
+/// Pitch accents with the same reading & positions from different dictionaries only once.
+pub fn unique_pitches(pitches: &[Pitch]) -> Vec<(&str, u8)> {
+    let mut unique: Vec<(&str, u8)> = Vec::new();
+    for pitch in pitches {
+        for &position in &pitch.positions {
+            if !unique.contains(&(pitch.reading.as_str(), position)) {
+                unique.push((&pitch.reading, position));
+            }
+        }
+    }
+    unique
+}
+
+/// The reading with an overline over its high morae, ie. `‾‾‾‾` above `はし`, & the downstep marked by `ꜜ`.
+/// Kana are double width, so each high mora gets two overline characters.
+pub fn overline_notation(reading: &str, position: u8) -> (String, String) {
+    let morae = morae(reading);
+    let pattern = pitch_pattern(morae.len(), position);
+
+    let mut overline = String::new();
+    let mut text = String::new();
+    for (i, (mora, high)) in morae.iter().zip(&pattern).enumerate() {
+        let width = mora.chars().count() * 2;
+        let mark = if *high { "‾" } else { " " };
+        overline.push_str(&mark.repeat(width));
+        text.push_str(mora);
+
+        if position as usize == i + 1 {
+            overline.push(' ');
+            text.push('ꜜ');
+        }
+    }
+    (overline, text)
+}
+
+/// Html for Anki, with high morae in an overlined span & a `ꜜ` after the downstep.
+pub fn pitch_html(reading: &str, position: u8) -> String {
+    let morae = morae(reading);
+    let pattern = pitch_pattern(morae.len(), position);
+
+    let mut html = String::new();
+    let mut in_high = false;
+    for (i, (mora, high)) in morae.iter().zip(&pattern).enumerate() {
+        if *high && !in_high {
+            html.push_str(r#"<span style="text-decoration:overline;">"#);
+        } else if !*high && in_high {
+            html.push_str("</span>");
+        }
+        in_high = *high;
+        html.push_str(mora);
+
+        if position as usize == i + 1 {
+            if in_high {
+                html.push_str("</span>");
+                in_high = false;
+            }
+            html.push('ꜜ');
+        }
+    }
+    if in_high {
+        html.push_str("</span>");
+    }
+    html
+}
+
+/// Every pitch accent of the expression as html, separated by `・`.
+pub fn pitches_html(pitches: &[Pitch]) -> Option<String> {
+    let html: Vec<String> = unique_pitches(pitches)
+        .into_iter()
+        .map(|(reading, position)| pitch_html(reading, position))
+        .collect();
+    if html.is_empty() {
+        return None;
+    }
+    Some(html.join("・"))
+}
@@ -0,0 +1,105 @@
+use crate::app::*;
+use crate::tasks::{TaskKind, TaskResult};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PrefetchConfig {
+    /// How many expressions after the selected one to fetch ahead, 0 disables prefetching.
+    pub window: usize,
+    /// Also download the first sentence's audio.
+    pub audio: bool,
+    /// Max prefetches running at the same time.
+    pub concurrency: usize,
+    /// Min time between two requests to Immersion Kit.
+    pub ik_min_interval_ms: u64,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self {
+            window: 3,
+            audio: false,
+            concurrency: 2,
+            ik_min_interval_ms: 250,
+        }
+    }
+}
+
+pub struct Prefetcher {
+    permits: Arc<Semaphore>,
+}
+
+impl Default for Prefetcher {
+    fn default() -> Self {
+        Self::new(&PrefetchConfig::default())
+    }
+}
+
+impl Prefetcher {
+    pub fn new(config: &PrefetchConfig) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(config.concurrency.max(1))),
+        }
+    }
+}
+
+impl AppState {
+    /// Starts fetching sentences for the expressions after `selected_expression`
+    /// that haven't been fetched yet.
+    pub fn prefetch_next(&mut self) {
+        let Some(selected) = self.selected_expression else {
+            return;
+        };
+        let window = self.config.prefetch.window;
+        let loading = self.tasks.loading_words();
+
+        let to_fetch: Vec<Expression> = self
+            .expressions
+            .iter()
+            .skip(selected + 1)
+            .filter(|exp| !self.config.frequency.is_hidden(exp.frequency_rank()))
+            .take(window)
+            .filter(|exp| exp.sentences.is_none() && !loading.contains(&exp.dict_word))
+            .cloned()
+            .collect();
+
+        for expression in to_fetch {
+            let dict_word = expression.dict_word.clone();
+            let sources = self.sources.clone();
+            let cache = self.cache.clone();
+            let permits = self.prefetcher.permits.clone();
+            let with_audio = self.config.prefetch.audio;
+
+            self.tasks
+                .spawn(TaskKind::Sentences, Some(dict_word.clone()), async move {
+                    let _permit = permits.acquire_owned().await;
+                    let instant = Instant::now();
+
+                    let mut result = sources
+                        .fetch(&expression, false)
+                        .await
+                        .map_err(|err| err.to_string());
+
+                    if let (true, Ok((res, _))) = (with_audio, &mut result) {
+                        if let Some(first) = res.sentences.first_mut() {
+                            if let (None, Some(audio_url)) = (&first.audio_data, &first.audio_url) {
+                                first.audio_data =
+                                    cache.fetch_bytes("audio", audio_url, audio_url).await.ok();
+                            }
+                        }
+                    }
+
+                    TaskResult::Sentences {
+                        dict_word,
+                        elapsed: instant.elapsed(),
+                        result,
+                        prefetched: true,
+                    }
+                });
+        }
+    }
+}
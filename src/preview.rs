@@ -0,0 +1,186 @@
+use crate::anki::{commit_note_updates, NoteAction, UpdatePlan};
+use crate::app::*;
+use crate::tasks::{TaskKind, TaskResult};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, List, ListItem, Paragraph, Wrap},
+};
+use std::time::Instant;
+
+impl AppState {
+    pub fn open_update_preview(&mut self, plan: UpdatePlan) {
+        self.info.msg = None;
+        let selected = (!plan.notes.is_empty()).then_some(0);
+        self.plan_state.select(selected);
+        self.update_plan = Some(plan);
+        self.selected_page = Pages::Preview;
+    }
+
+    pub fn handle_preview_keybinds(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let Some(plan) = self.update_plan.as_mut() else {
+            self.selected_page = Pages::Main;
+            return;
+        };
+        let len = plan.notes.len();
+        let selected = self.plan_state.selected().unwrap_or(0);
+
+        match key.code {
+            KeyCode::Up if len > 0 => {
+                let i = selected.checked_sub(1).unwrap_or(len - 1);
+                self.plan_state.select(Some(i));
+            }
+            KeyCode::Down if len > 0 => {
+                let i = if selected + 1 >= len { 0 } else { selected + 1 };
+                self.plan_state.select(Some(i));
+            }
+            KeyCode::Char(' ') => {
+                if let Some(note) = plan.notes.get_mut(selected) {
+                    note.approved = !note.approved;
+                }
+            }
+            KeyCode::Char('A') => {
+                let approve = !plan.notes.iter().all(|note| note.approved);
+                for note in plan.notes.iter_mut() {
+                    note.approved = approve;
+                }
+            }
+            KeyCode::Enter => self.commit_update_plan(),
+            KeyCode::Esc => self.cancel_update_plan(),
+            _ => {}
+        }
+    }
+
+    /// Writes the approved notes, the rest go back to the notes list.
+    fn commit_update_plan(&mut self) {
+        let Some(mut plan) = self.update_plan.take() else {
+            return;
+        };
+        let unapproved = plan.take_unapproved();
+        self.notes_to_be_created.sentences.extend(unapproved);
+        self.selected_page = Pages::Main;
+
+        if plan.notes.is_empty() {
+            self.info.msg = Some("No Notes Approved".to_string());
+            return;
+        }
+
+        self.info.msg = Some(format!("Updating {} Note(s)", plan.notes.len()));
+        self.tasks.spawn(TaskKind::UpdateCards, None, async move {
+            let instant = Instant::now();
+            let res = commit_note_updates(plan).await;
+
+            TaskResult::UpdateCards {
+                elapsed: instant.elapsed(),
+                res,
+            }
+        });
+    }
+
+    /// Puts every sentence back in the notes list, nothing is written.
+    fn cancel_update_plan(&mut self) {
+        if let Some(plan) = self.update_plan.take() {
+            let mut sentences = plan.into_sentences();
+            sentences.append(&mut self.notes_to_be_created.sentences);
+            self.notes_to_be_created.sentences = sentences;
+        }
+        self.info.msg = Some("Update Cancelled".to_string());
+        self.selected_page = Pages::Main;
+    }
+
+    pub fn rend_update_preview(&mut self, area: Rect, buf: &mut Buffer) {
+        let Some(plan) = &self.update_plan else {
+            return;
+        };
+        let horizontal =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]);
+        let [list_area, diff_area] = horizontal.areas(area);
+
+        let items: Vec<ListItem> = plan
+            .notes
+            .iter()
+            .map(|note| {
+                let check = if note.approved { "[x] " } else { "[ ] " };
+                let color = match note.action {
+                    NoteAction::Update(_) => Color::Green,
+                    NoteAction::Add => Color::Cyan,
+                    NoteAction::Skip(_) => Color::Red,
+                };
+                ListItem::new(vec![
+                    Line::from(vec![
+                        Span::raw(check),
+                        Span::styled(
+                            format!("{} ", note.sentence().parent_expression.dict_word),
+                            Style::default().yellow(),
+                        ),
+                        Span::styled(note.action.to_string(), color),
+                    ]),
+                    Line::from(Span::styled(
+                        format!("    {}", note.sentence().sentence),
+                        Style::default().dim(),
+                    )),
+                ])
+            })
+            .collect();
+
+        let approved = plan.notes.iter().filter(|note| note.approved).count();
+        let title = Line::from(vec![
+            Span::styled(
+                format!("Preview [{}/{}] ", approved, plan.notes.len()),
+                Style::default().yellow(),
+            ),
+            Span::styled(
+                "<Space> Approve Note <A> Approve All <Enter> Update <Esc> Cancel",
+                Style::default().white(),
+            ),
+        ]);
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .style(Style::default().green()),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED).dim());
+
+        let mut lines: Vec<Line> = Vec::new();
+        if let Some(note) = self.plan_state.selected().and_then(|i| plan.notes.get(i)) {
+            if let NoteAction::Skip(outcome) = &note.action {
+                lines.push(Line::styled(outcome.to_string(), Color::Red));
+            }
+            if let Some(err) = note.media_error() {
+                lines.push(Line::styled(
+                    format!("Without media: {}", err),
+                    Color::Yellow,
+                ));
+            }
+            for change in &note.changes {
+                lines.push(Line::styled(
+                    change.field.clone(),
+                    Style::default().yellow().bold(),
+                ));
+                if !change.is_changed() {
+                    lines.push(Line::styled(
+                        format!("  {}", change.new),
+                        Style::default().dim(),
+                    ));
+                    continue;
+                }
+                if let Some(current) = change.current.as_ref().filter(|c| !c.is_empty()) {
+                    lines.push(Line::styled(format!("- {}", current), Color::Red));
+                }
+                lines.push(Line::styled(format!("+ {}", change.new), Color::Green));
+            }
+        }
+        let diff = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::bordered()
+                .title("Changes")
+                .style(Style::default().white()),
+        );
+
+        StatefulWidget::render(list, list_area, buf, &mut self.plan_state);
+        Widget::render(diff, diff_area, buf);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::anki::{NoteOutcome, NoteResult};
+use crate::app::*;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, List, ListItem},
+};
+
+impl AppState {
+    pub fn open_update_results(&mut self, results: Vec<NoteResult>) {
+        self.update_results = results;
+        let selected = (!self.update_results.is_empty()).then_some(0);
+        self.results_state.select(selected);
+        self.selected_page = Pages::Results;
+    }
+
+    pub fn handle_results_keybinds(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let len = self.update_results.len();
+        let selected = self.results_state.selected().unwrap_or(0);
+
+        match key.code {
+            KeyCode::Up if len > 0 => {
+                let i = selected.checked_sub(1).unwrap_or(len - 1);
+                self.results_state.select(Some(i));
+            }
+            KeyCode::Down if len > 0 => {
+                let i = if selected + 1 >= len { 0 } else { selected + 1 };
+                self.results_state.select(Some(i));
+            }
+            KeyCode::Char('N') => {
+                self.selected_page = Pages::Main;
+                if !self.notes_to_be_created.sentences.is_empty() {
+                    self.notes_to_be_created.state.select(Some(0));
+                    self.select_mode = SelectMode::Ntbm;
+                }
+            }
+            KeyCode::Esc => self.selected_page = Pages::Main,
+            _ => {}
+        }
+    }
+
+    pub fn rend_update_results(&mut self, area: Rect, buf: &mut Buffer) {
+        let items: Vec<ListItem> = self
+            .update_results
+            .iter()
+            .map(|result| {
+                let (mark, color) = match result.outcome {
+                    NoteOutcome::Updated(_) | NoteOutcome::Created(_) => ("✓ ", Color::Green),
+                    NoteOutcome::MediaFailed(..) => ("! ", Color::Yellow),
+                    _ => ("✗ ", Color::Red),
+                };
+                let sentence = &result.sentence;
+                ListItem::new(vec![
+                    Line::from(vec![
+                        Span::styled(mark, color),
+                        Span::styled(
+                            format!("{} ", sentence.parent_expression.dict_word),
+                            Style::default().yellow(),
+                        ),
+                        Span::styled(result.outcome.to_string(), color),
+                    ]),
+                    Line::from(Span::styled(
+                        format!("  {}", sentence.sentence),
+                        Style::default().dim(),
+                    )),
+                ])
+            })
+            .collect();
+
+        let title = Line::from(vec![
+            Span::styled("Update Results ", Style::default().yellow()),
+            Span::styled(
+                "<N> Retry Failed Notes <Esc> Go Back",
+                Style::default().white(),
+            ),
+        ]);
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .style(Style::default().green()),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED).dim());
+
+        StatefulWidget::render(list, area, buf, &mut self.results_state);
+    }
+}
@@ -0,0 +1,232 @@
+pub mod novels;
+pub mod subtitles;
+pub mod tatoeba;
+
+use crate::anki::ConfigJson;
+use crate::app::{Expression, Sentence};
+use crate::deinflect::find_inflected;
+use crate::fetch::{ImmersionKitSource, MassifSource};
+use crate::filters::Facet;
+use futures_util::future::BoxFuture;
+use novels::NovelSource;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use subtitles::SubtitleSource;
+use tatoeba::TatoebaSource;
+use tokio::sync::Mutex;
+
+pub type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Sources used when `sources` is missing or empty in `config.json`.
+pub const DEFAULT_SOURCES: [&str; 2] = ["immersion_kit", "massif"];
+
+#[derive(Default, Debug)]
+pub struct SourceResult {
+    pub sentences: Vec<Sentence>,
+    pub definitions: Vec<String>,
+    pub readings: Vec<String>,
+    /// Result counts per category & deck, used by the filter picker.
+    pub facets: Vec<Facet>,
+}
+
+/// A provider of example sentences for an `Expression`.
+///
+/// Sources are looked up by `name()` from the `sources` list in `config.json`,
+/// and are tried in that order until one of them returns sentences.
+pub trait SentenceSource: Send + Sync {
+    /// The key used for this source in `config.json`.
+    fn name(&self) -> &'static str;
+
+    /// Shown in the information area after a successful fetch.
+    fn label(&self) -> &'static str;
+
+    fn fetch<'a>(
+        &'a self,
+        expression: &'a Expression,
+    ) -> BoxFuture<'a, Result<SourceResult, SourceError>>;
+}
+
+#[derive(Clone, Default)]
+pub struct SourceRegistry {
+    chain: Vec<Arc<dyn SentenceSource>>,
+}
+
+impl SourceRegistry {
+    pub fn new(chain: Vec<Arc<dyn SentenceSource>>) -> Self {
+        Self { chain }
+    }
+
+    /// Builds the fallback chain from `config.sources`.
+    /// Unknown source names are skipped and returned as errors.
+    pub fn from_config(config: &ConfigJson) -> (Self, Vec<String>) {
+        let names: Vec<String> = if config.sources.is_empty() {
+            DEFAULT_SOURCES.iter().map(|s| s.to_string()).collect()
+        } else {
+            config.sources.clone()
+        };
+
+        let mut chain: Vec<Arc<dyn SentenceSource>> = Vec::new();
+        let mut errors = Vec::new();
+
+        for name in names {
+            match build_source(&name, config) {
+                Some(source) if chain.iter().any(|s| s.name() == source.name()) => {
+                    errors.push(format!("Duplicate Sentence Source: `{}`", name))
+                }
+                Some(source) => chain.push(source),
+                None => errors.push(format!("Unknown Sentence Source: `{}`", name)),
+            }
+        }
+
+        (Self::new(chain), errors)
+    }
+
+    /// The `name()` of each source, in fallback order.
+    #[cfg(test)]
+    pub fn names(&self) -> Vec<&'static str> {
+        self.chain.iter().map(|source| source.name()).collect()
+    }
+
+    /// Tries every source in order, returning the first non-empty result
+    /// along with the label of the source that produced it.
+    /// Definitions & readings are merged from every source that was tried.
+    /// `skip_ik` leaves out Immersion Kit, unless it's the only source.
+    pub async fn fetch(
+        &self,
+        expression: &Expression,
+        skip_ik: bool,
+    ) -> Result<(SourceResult, &'static str), SourceError> {
+        let skip_ik = skip_ik && self.chain.iter().any(|s| s.name() != "immersion_kit");
+        let mut merged = SourceResult::default();
+        let mut errors: Vec<String> = Vec::new();
+
+        for source in self.chain.iter() {
+            if skip_ik && source.name() == "immersion_kit" {
+                continue;
+            }
+
+            match source.fetch(expression).await {
+                Ok(res) => {
+                    merged.definitions.extend(res.definitions);
+                    for reading in res.readings {
+                        if !merged.readings.contains(&reading) {
+                            merged.readings.push(reading);
+                        }
+                    }
+
+                    if !res.sentences.is_empty() {
+                        merged.sentences = res.sentences;
+                        return Ok((merged, source.label()));
+                    }
+                }
+                Err(err) => errors.push(format!("{}: {}", source.label(), err)),
+            }
+        }
+
+        if errors.is_empty() {
+            return Err("0 Sentences Found!".into());
+        }
+
+        Err(errors.join(" | ").into())
+    }
+}
+
+fn build_source(name: &str, config: &ConfigJson) -> Option<Arc<dyn SentenceSource>> {
+    match name {
+        "immersion_kit" => Some(Arc::new(ImmersionKitSource::new(config))),
+        "massif" => Some(Arc::new(MassifSource::new(config))),
+        "tatoeba" => Some(Arc::new(TatoebaSource::new(&config.tatoeba))),
+        "subtitles" => Some(Arc::new(SubtitleSource::new(&config.subtitles))),
+        "novels" => Some(Arc::new(NovelSource::new(&config.novels))),
+        _ => None,
+    }
+}
+
+/// Sentences read from local files, searched by `find_matches`.
+pub trait SentenceIndex: Send + Sync + 'static {
+    /// The text of every sentence, in the order they're returned.
+    fn texts(&self) -> impl Iterator<Item = &str>;
+}
+
+/// A `SentenceIndex` that's rebuilt once the files it was built from change.
+pub struct LocalIndex<T> {
+    /// The index & the stamp of its files when it was built.
+    index: Mutex<Option<(SystemTime, Arc<T>)>>,
+}
+
+impl<T: SentenceIndex> Default for LocalIndex<T> {
+    fn default() -> Self {
+        Self {
+            index: Mutex::new(None),
+        }
+    }
+}
+
+impl<T: SentenceIndex> LocalIndex<T> {
+    /// Returns the index, building it off the runtime's threads if it doesn't exist yet
+    /// or `stamp` is newer than when it was built. Without a stamp it's always rebuilt.
+    pub async fn get(
+        &self,
+        stamp: impl FnOnce() -> Option<SystemTime> + Send + 'static,
+        build: impl FnOnce() -> Result<T, SourceError> + Send + 'static,
+    ) -> Result<Arc<T>, SourceError> {
+        let mut index = self.index.lock().await;
+
+        let stamp = tokio::task::spawn_blocking(stamp).await?;
+        if let (Some(stamp), Some((built, built_index))) = (stamp, index.as_ref()) {
+            if stamp <= *built {
+                return Ok(built_index.clone());
+            }
+        }
+
+        let built_index = Arc::new(tokio::task::spawn_blocking(build).await??);
+        *index = Some((stamp.unwrap_or(SystemTime::UNIX_EPOCH), built_index.clone()));
+        Ok(built_index)
+    }
+}
+
+/// Positions of the first `max` sentences in `index` that contain `word`, inflected or not.
+pub async fn find_matches<T: SentenceIndex>(
+    index: Arc<T>,
+    word: &str,
+    max: usize,
+) -> Result<Vec<usize>, SourceError> {
+    let word = word.trim().to_string();
+
+    // scanning the whole index would stall the runtime's threads
+    let matches = tokio::task::spawn_blocking(move || {
+        index
+            .texts()
+            .enumerate()
+            .filter(|(_, text)| find_inflected(text, &word).is_some())
+            .map(|(i, _)| i)
+            .take(max)
+            .collect()
+    })
+    .await?;
+
+    Ok(matches)
+}
+
+/// The newest mtime of `dir` & everything in it,
+/// adding or removing a file changes its directory's mtime.
+pub fn newest_mtime(dir: &Path) -> Option<SystemTime> {
+    let mut newest = dir.metadata().ok()?.modified().ok()?;
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+            if let Ok(modified) = path.metadata().and_then(|meta| meta.modified()) {
+                newest = newest.max(modified);
+            }
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+    }
+    Some(newest)
+}
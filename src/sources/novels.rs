@@ -0,0 +1,323 @@
+use crate::app::{Expression, Sentence};
+use crate::cmds::write_to_errs_log;
+use crate::sources::{
+    find_matches, newest_mtime, LocalIndex, SentenceIndex, SentenceSource, SourceError,
+    SourceResult,
+};
+use futures_util::future::BoxFuture;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Max sentences returned for a single expression.
+const MAX_RESULTS: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct NovelsConfig {
+    pub dir: String,
+}
+
+impl Default for NovelsConfig {
+    fn default() -> Self {
+        Self {
+            dir: "data/novels".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BookSentence {
+    pub text: String,
+    pub book: usize,
+    /// `chapter.xhtml:line` for epubs, `L{line}` for text files.
+    pub position: String,
+}
+
+#[derive(Debug)]
+pub struct Book {
+    pub title: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Default)]
+pub struct NovelIndex {
+    pub books: Vec<Book>,
+    pub sentences: Vec<BookSentence>,
+}
+
+impl SentenceIndex for NovelIndex {
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        self.sentences.iter().map(|s| s.text.as_str())
+    }
+}
+
+pub struct NovelSource {
+    config: NovelsConfig,
+    index: LocalIndex<NovelIndex>,
+}
+
+impl NovelSource {
+    pub fn new(config: &NovelsConfig) -> Self {
+        Self {
+            config: config.clone(),
+            index: LocalIndex::default(),
+        }
+    }
+
+    async fn search(&self, parent_expression: &Expression) -> Result<SourceResult, SourceError> {
+        // rebuilt once a book is added, removed or changed
+        let dir = PathBuf::from(&self.config.dir);
+        let stamp_dir = dir.clone();
+        let index = self
+            .index
+            .get(
+                move || newest_mtime(&stamp_dir),
+                move || {
+                    let (index, skipped) = build_index(&dir)?;
+                    // one bad book shouldn't hide the rest
+                    if !skipped.is_empty() {
+                        let _ = write_to_errs_log(&skipped);
+                    }
+                    Ok(index)
+                },
+            )
+            .await?;
+        let matches =
+            find_matches(index.clone(), &parent_expression.dict_word, MAX_RESULTS).await?;
+
+        let sentences: Vec<Sentence> = matches
+            .into_iter()
+            .map(|i| {
+                let s = &index.sentences[i];
+                let book = &index.books[s.book];
+                let wbst_link = format!("file://{}#{}", book.path.display(), s.position);
+                Sentence::from(
+                    &s.text,
+                    None,
+                    None,
+                    None,
+                    &book.title,
+                    &wbst_link,
+                    parent_expression,
+                )
+            })
+            .collect();
+
+        Ok(SourceResult {
+            sentences,
+            ..SourceResult::default()
+        })
+    }
+}
+
+impl SentenceSource for NovelSource {
+    fn name(&self) -> &'static str {
+        "novels"
+    }
+
+    fn label(&self) -> &'static str {
+        "Novel"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        expression: &'a Expression,
+    ) -> BoxFuture<'a, Result<SourceResult, SourceError>> {
+        Box::pin(self.search(expression))
+    }
+}
+
+/// Reads every epub & text file in `dir`, along with an error for each book that couldn't be read.
+pub fn build_index(dir: &Path) -> Result<(NovelIndex, Vec<String>), SourceError> {
+    if !dir.is_dir() {
+        return Err(format!("Novel directory `{}` not found", dir.display()).into());
+    }
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    paths.sort();
+
+    let mut index = NovelIndex::default();
+    let mut skipped = Vec::new();
+
+    for path in paths {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        let path = path.canonicalize().unwrap_or(path);
+
+        let (title, chapters) = match ext.as_deref() {
+            Some("epub") => match read_epub(&path) {
+                Ok(book) => book,
+                Err(err) => {
+                    skipped.push(format!("Skipped novel `{}`: {}", path.display(), err));
+                    continue;
+                }
+            },
+            Some("txt") => {
+                let title = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let text = match std::fs::read(&path) {
+                    Ok(bytes) => decode_text(&bytes),
+                    Err(err) => {
+                        skipped.push(format!("Skipped novel `{}`: {}", path.display(), err));
+                        continue;
+                    }
+                };
+                (title, vec![(String::new(), text)])
+            }
+            _ => continue,
+        };
+
+        let book = index.books.len();
+        for (href, text) in chapters {
+            for (line_i, line) in text.lines().enumerate() {
+                let position = if href.is_empty() {
+                    format!("L{}", line_i + 1)
+                } else {
+                    format!("{}:{}", href, line_i + 1)
+                };
+
+                index
+                    .sentences
+                    .extend(split_sentences(line).into_iter().map(|text| BookSentence {
+                        text,
+                        book,
+                        position: position.clone(),
+                    }));
+            }
+        }
+
+        index.books.push(Book { title, path });
+    }
+
+    Ok((index, skipped))
+}
+
+/// Returns the book title & its chapters (href, plain text) in spine order.
+fn read_epub(path: &Path) -> Result<(String, Vec<(String, String)>), SourceError> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+    let container = read_zip_entry(&mut archive, "META-INF/container.xml")?;
+    let opf_path = Regex::new(r#"full-path="([^"]+)""#)?
+        .captures(&container)
+        .map(|cap| cap[1].to_string())
+        .ok_or("epub container has no rootfile")?;
+    let opf_dir = opf_path
+        .rsplit_once('/')
+        .map(|(dir, _)| format!("{}/", dir))
+        .unwrap_or_default();
+    let opf = read_zip_entry(&mut archive, &opf_path)?;
+
+    let title = Regex::new(r"<dc:title[^>]*>([^<]+)</dc:title>")?
+        .captures(&opf)
+        .map(|cap| decode_entities(cap[1].trim()))
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+
+    let item_re = Regex::new(r"<item\s[^>]*>")?;
+    let id_re = Regex::new(r#"\sid="([^"]+)""#)?;
+    let href_re = Regex::new(r#"\shref="([^"]+)""#)?;
+    let manifest: Vec<(String, String)> = item_re
+        .find_iter(&opf)
+        .filter_map(|item| {
+            let item = item.as_str();
+            let id = id_re.captures(item)?[1].to_string();
+            let href = href_re.captures(item)?[1].to_string();
+            Some((id, href))
+        })
+        .collect();
+
+    let spine_re = Regex::new(r#"<itemref\s[^>]*idref="([^"]+)""#)?;
+    let mut chapters = Vec::new();
+    for cap in spine_re.captures_iter(&opf) {
+        let Some((_, href)) = manifest.iter().find(|(id, _)| *id == cap[1]) else {
+            continue;
+        };
+        let Ok(html) = read_zip_entry(&mut archive, &format!("{}{}", opf_dir, href)) else {
+            continue;
+        };
+        chapters.push((href.clone(), html_to_text(&html)));
+    }
+
+    Ok((title, chapters))
+}
+
+fn read_zip_entry(archive: &mut zip::ZipArchive<File>, name: &str) -> Result<String, SourceError> {
+    let mut entry = archive.by_name(name)?;
+    let mut buf = Vec::new();
+    entry.read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+/// Strips tags & ruby readings, keeping block elements on their own lines.
+fn html_to_text(html: &str) -> String {
+    let body = html.split_once("<body").map_or(html, |(_, body)| body);
+    let ruby_re = Regex::new(r"(?s)<rt[^>]*>.*?</rt>|<rp[^>]*>.*?</rp>").unwrap();
+    let block_re = Regex::new(r"(?i)</p>|<br\s*/?>|</div>|</h[1-6]>").unwrap();
+    let tag_re = Regex::new(r"(?s)<[^>]*>").unwrap();
+
+    let text = ruby_re.replace_all(body, "");
+    let text = block_re.replace_all(&text, "\n");
+    let text = tag_re.replace_all(&text, "");
+    decode_entities(&text)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Text files are usually utf-8, older ones (ie. Aozora Bunko) are Shift-JIS.
+fn decode_text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.trim_start_matches('\u{feff}').to_string(),
+        Err(_) => encoding_rs::SHIFT_JIS.decode(bytes).0.to_string(),
+    }
+}
+
+/// Splits a paragraph after `。！？`, keeping closing brackets with their sentence.
+fn split_sentences(line: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = line.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        current.push(c);
+        if !matches!(c, '。' | '！' | '？' | '!' | '?') {
+            continue;
+        }
+        while let Some(&next) = chars.peek() {
+            if !matches!(next, '」' | '』' | '）' | ')' | '！' | '？') {
+                break;
+            }
+            current.push(next);
+            chars.next();
+        }
+        let sentence = current.trim().to_string();
+        if !sentence.is_empty() {
+            sentences.push(sentence);
+        }
+        current.clear();
+    }
+
+    let rest = current.trim();
+    if !rest.is_empty() {
+        sentences.push(rest.to_string());
+    }
+    sentences
+}
@@ -0,0 +1,297 @@
+use crate::app::{Expression, Sentence};
+use crate::sources::{
+    find_matches, newest_mtime, LocalIndex, SentenceIndex, SentenceSource, SourceError,
+    SourceResult,
+};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Max sentences returned for a single expression.
+const MAX_RESULTS: usize = 50;
+
+const MEDIA_EXTS: [&str; 10] = [
+    "mkv", "mp4", "webm", "avi", "mp3", "m4a", "flac", "ogg", "opus", "wav",
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SubtitlesConfig {
+    pub dir: String,
+    pub ffmpeg: String,
+}
+
+impl Default for SubtitlesConfig {
+    fn default() -> Self {
+        Self {
+            dir: "data/subtitles".to_string(),
+            ffmpeg: "ffmpeg".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SubtitleLine {
+    text: String,
+    start: Duration,
+    end: Duration,
+    file: PathBuf,
+    media: Option<PathBuf>,
+}
+
+/// A part of a local media file, only cut once it's played or added to Anki.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioClip {
+    pub ffmpeg: String,
+    pub media: PathBuf,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl AudioClip {
+    /// Cuts the clip out of its media as mp3 bytes.
+    pub async fn cut(&self) -> Result<Vec<u8>, SourceError> {
+        let output = tokio::process::Command::new(&self.ffmpeg)
+            .args(["-loglevel", "error", "-ss"])
+            .arg(format!("{:.3}", self.start.as_secs_f64()))
+            .arg("-to")
+            .arg(format!("{:.3}", self.end.as_secs_f64()))
+            .arg("-i")
+            .arg(&self.media)
+            .args(["-vn", "-map", "0:a:0", "-f", "mp3", "pipe:1"])
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        if !output.status.success() || output.stdout.is_empty() {
+            return Err(String::from_utf8_lossy(&output.stderr).to_string().into());
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+impl SentenceIndex for Vec<SubtitleLine> {
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|line| line.text.as_str())
+    }
+}
+
+pub struct SubtitleSource {
+    config: SubtitlesConfig,
+    index: LocalIndex<Vec<SubtitleLine>>,
+}
+
+impl SubtitleSource {
+    pub fn new(config: &SubtitlesConfig) -> Self {
+        Self {
+            config: config.clone(),
+            index: LocalIndex::default(),
+        }
+    }
+
+    async fn search(&self, parent_expression: &Expression) -> Result<SourceResult, SourceError> {
+        // rebuilt once a subtitle file is added, removed or changed
+        let dir = PathBuf::from(&self.config.dir);
+        let stamp_dir = dir.clone();
+        let index = self
+            .index
+            .get(move || newest_mtime(&stamp_dir), move || build_index(&dir))
+            .await?;
+        let matches =
+            find_matches(index.clone(), &parent_expression.dict_word, MAX_RESULTS).await?;
+
+        let sentences: Vec<Sentence> = matches
+            .into_iter()
+            .map(|i| self.line_to_sentence(&index[i], parent_expression))
+            .collect();
+
+        Ok(SourceResult {
+            sentences,
+            ..SourceResult::default()
+        })
+    }
+
+    fn line_to_sentence(&self, line: &SubtitleLine, parent_expression: &Expression) -> Sentence {
+        let media_title = line
+            .file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut sentence = Sentence::from(
+            &line.text,
+            None,
+            None,
+            None,
+            &media_title,
+            &line.file.to_string_lossy(),
+            parent_expression,
+        );
+        sentence.timestamps = Some((line.start, line.end));
+        sentence.clip = line.media.as_ref().map(|media| AudioClip {
+            ffmpeg: self.config.ffmpeg.clone(),
+            media: media.clone(),
+            start: line.start,
+            end: line.end,
+        });
+        sentence
+    }
+}
+
+impl SentenceSource for SubtitleSource {
+    fn name(&self) -> &'static str {
+        "subtitles"
+    }
+
+    fn label(&self) -> &'static str {
+        "Subtitle"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        expression: &'a Expression,
+    ) -> BoxFuture<'a, Result<SourceResult, SourceError>> {
+        Box::pin(self.search(expression))
+    }
+}
+
+fn build_index(dir: &Path) -> Result<Vec<SubtitleLine>, SourceError> {
+    if !dir.is_dir() {
+        return Err(format!("Subtitle directory `{}` not found", dir.display()).into());
+    }
+
+    let mut files = Vec::new();
+    collect_subtitle_files(dir, &mut files)?;
+    files.sort();
+
+    let mut lines = Vec::new();
+    for file in files {
+        let content = match std::fs::read(&file) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+            Err(_) => continue,
+        };
+        let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+        let media = find_media_file(&file);
+
+        let parsed = match file.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("srt") => parse_srt(&content),
+            _ => parse_ass(&content),
+        };
+
+        lines.extend(parsed.into_iter().map(|(start, end, text)| SubtitleLine {
+            text,
+            start,
+            end,
+            file: file.clone(),
+            media: media.clone(),
+        }));
+    }
+
+    Ok(lines)
+}
+
+fn collect_subtitle_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_subtitle_files(&path, files)?;
+            continue;
+        }
+
+        let is_subtitle = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("srt") || ext.eq_ignore_ascii_case("ass"));
+        if is_subtitle {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Looks for a video or audio file next to the subtitle with the same name,
+/// also trying without a language suffix (ie. `ep01.ja.srt` -> `ep01.mkv`).
+fn find_media_file(subtitle: &Path) -> Option<PathBuf> {
+    let dir = subtitle.parent()?;
+    let stem = subtitle.file_stem()?.to_string_lossy().to_string();
+
+    let mut stems = vec![stem.clone()];
+    if let Some((short, _lang)) = stem.rsplit_once('.') {
+        stems.push(short.to_string());
+    }
+
+    stems.iter().find_map(|stem| {
+        MEDIA_EXTS
+            .iter()
+            .map(|ext| dir.join(format!("{}.{}", stem, ext)))
+            .find(|path| path.is_file())
+    })
+}
+
+pub fn parse_srt(content: &str) -> Vec<(Duration, Duration, String)> {
+    let tag_re = regex::Regex::new(r"<[^>]+>|\{[^}]*\}").unwrap();
+
+    content
+        .split("\n\n")
+        .filter_map(|block| {
+            let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+            let (start, end) = lines.next()?.split_once("-->")?;
+            let (start, end) = (parse_timestamp(start)?, parse_timestamp(end)?);
+
+            let text: String = lines
+                .map(|line| tag_re.replace_all(line.trim(), ""))
+                .collect();
+            if text.is_empty() {
+                return None;
+            }
+            Some((start, end, text))
+        })
+        .collect()
+}
+
+pub fn parse_ass(content: &str) -> Vec<(Duration, Duration, String)> {
+    let tag_re = regex::Regex::new(r"\{[^}]*\}").unwrap();
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields = line.strip_prefix("Dialogue:")?;
+            // Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+            let cols: Vec<&str> = fields.splitn(10, ',').collect();
+            if cols.len() < 10 {
+                return None;
+            }
+
+            let (start, end) = (parse_timestamp(cols[1])?, parse_timestamp(cols[2])?);
+            let text = tag_re
+                .replace_all(cols[9], "")
+                .replace("\\N", "")
+                .replace("\\n", "")
+                .replace("\\h", " ")
+                .trim()
+                .to_string();
+            if text.is_empty() {
+                return None;
+            }
+            Some((start, end, text))
+        })
+        .collect()
+}
+
+/// Parses `HH:MM:SS,mmm` (srt) & `H:MM:SS.cc` (ass) timestamps.
+pub fn parse_timestamp(stamp: &str) -> Option<Duration> {
+    let mut parts = stamp.trim().splitn(3, ':');
+    let hours: u64 = parts.next()?.parse().ok()?;
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let (secs, frac) = parts.next()?.split_once(['.', ','])?;
+    let secs: u64 = secs.parse().ok()?;
+
+    let frac: String = frac.chars().chain("000".chars()).take(3).collect();
+    let millis: u64 = frac.parse().ok()?;
+
+    Some(Duration::from_millis(
+        ((hours * 60 + minutes) * 60 + secs) * 1000 + millis,
+    ))
+}
@@ -0,0 +1,225 @@
+use crate::app::{Expression, Sentence};
+use crate::sources::{
+    find_matches, LocalIndex, SentenceIndex, SentenceSource, SourceError, SourceResult,
+};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::Path;
+
+/// Max sentences returned for a single expression.
+const MAX_RESULTS: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TatoebaConfig {
+    pub sentences_csv: String,
+    pub links_csv: String,
+    pub index_path: String,
+}
+
+impl Default for TatoebaConfig {
+    fn default() -> Self {
+        Self {
+            sentences_csv: "data/tatoeba/sentences.csv".to_string(),
+            links_csv: "data/tatoeba/links.csv".to_string(),
+            index_path: "data/tatoeba/index.json".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TatoebaEntry {
+    pub id: u64,
+    pub text: String,
+    pub translations: Vec<String>,
+}
+
+impl SentenceIndex for Vec<TatoebaEntry> {
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|entry| entry.text.as_str())
+    }
+}
+
+pub struct TatoebaSource {
+    config: TatoebaConfig,
+    index: LocalIndex<Vec<TatoebaEntry>>,
+}
+
+impl TatoebaSource {
+    pub fn new(config: &TatoebaConfig) -> Self {
+        Self {
+            config: config.clone(),
+            index: LocalIndex::default(),
+        }
+    }
+
+    async fn search(&self, parent_expression: &Expression) -> Result<SourceResult, SourceError> {
+        // reloaded once the csv dumps are replaced
+        let stamp_config = self.config.clone();
+        let config = self.config.clone();
+        let index = self
+            .index
+            .get(
+                move || {
+                    let mtime = |path: &str| Path::new(path).metadata().ok()?.modified().ok();
+                    let csvs =
+                        mtime(&stamp_config.sentences_csv).max(mtime(&stamp_config.links_csv));
+                    // an index shipped without its dumps
+                    csvs.or_else(|| mtime(&stamp_config.index_path))
+                },
+                move || load_or_build_index(&config),
+            )
+            .await?;
+        let matches =
+            find_matches(index.clone(), &parent_expression.dict_word, MAX_RESULTS).await?;
+
+        let sentences: Vec<Sentence> = matches
+            .into_iter()
+            .map(|i| {
+                let entry = &index[i];
+                let wbst_link = format!("https://tatoeba.org/en/sentences/show/{}", entry.id);
+                let mut sentence = Sentence::from(
+                    &entry.text,
+                    None,
+                    None,
+                    None,
+                    "Tatoeba",
+                    &wbst_link,
+                    parent_expression,
+                );
+                if !entry.translations.is_empty() {
+                    sentence.translation = Some(entry.translations.join(" / "));
+                }
+                sentence
+            })
+            .collect();
+
+        Ok(SourceResult {
+            sentences,
+            ..SourceResult::default()
+        })
+    }
+}
+
+impl SentenceSource for TatoebaSource {
+    fn name(&self) -> &'static str {
+        "tatoeba"
+    }
+
+    fn label(&self) -> &'static str {
+        "Tatoeba"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        expression: &'a Expression,
+    ) -> BoxFuture<'a, Result<SourceResult, SourceError>> {
+        Box::pin(self.search(expression))
+    }
+}
+
+/// Reads the index from `index_path`, building it from the csv dumps first if it doesn't exist yet
+/// or they're newer.
+pub fn load_or_build_index(config: &TatoebaConfig) -> Result<Vec<TatoebaEntry>, SourceError> {
+    let index_path = Path::new(&config.index_path);
+    let csvs = [
+        Path::new(&config.sentences_csv),
+        Path::new(&config.links_csv),
+    ];
+
+    let is_fresh = match index_path.metadata() {
+        Ok(index) => {
+            let built = index.modified()?;
+            csvs.iter()
+                .filter_map(|csv| csv.metadata().ok())
+                .all(|csv| csv.modified().is_ok_and(|modified| modified <= built))
+        }
+        Err(_) => false,
+    };
+    if is_fresh {
+        let reader = BufReader::new(File::open(index_path)?);
+        return Ok(serde_json::from_reader(reader)?);
+    }
+
+    if !csvs.iter().all(|csv| csv.exists()) {
+        return Err(format!(
+            "No index found. Download `sentences.csv` & `links.csv` from tatoeba.org/downloads into `{}`",
+            index_path.parent().unwrap_or(Path::new(".")).display()
+        )
+        .into());
+    }
+
+    let entries = build_index(&config.sentences_csv, &config.links_csv)?;
+
+    if let Some(parent) = index_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let writer = BufWriter::new(File::create(index_path)?);
+    serde_json::to_writer(writer, &entries)?;
+
+    Ok(entries)
+}
+
+/// Joins the Japanese sentences from `sentences.csv` with their English translations from `links.csv`.
+/// Both dumps are tab separated: `id  lang  text` & `sentence_id  translation_id`.
+pub fn build_index(sentences_csv: &str, links_csv: &str) -> Result<Vec<TatoebaEntry>, SourceError> {
+    let mut jpn: HashMap<u64, String> = HashMap::new();
+    let mut eng: HashMap<u64, String> = HashMap::new();
+
+    for line in BufReader::new(File::open(sentences_csv)?).lines() {
+        let line = line?;
+        let mut cols = line.splitn(3, '\t');
+        let (Some(id), Some(lang), Some(text)) = (cols.next(), cols.next(), cols.next()) else {
+            continue;
+        };
+        let Ok(id) = id.parse::<u64>() else {
+            continue;
+        };
+
+        match lang {
+            "jpn" => jpn.insert(id, text.to_string()),
+            "eng" => eng.insert(id, text.to_string()),
+            _ => None,
+        };
+    }
+
+    let mut translations: HashMap<u64, Vec<String>> = HashMap::new();
+    for line in BufReader::new(File::open(links_csv)?).lines() {
+        let line = line?;
+        let Some((from, to)) = line.split_once('\t') else {
+            continue;
+        };
+        let (Ok(from), Ok(to)) = (from.trim().parse::<u64>(), to.trim().parse::<u64>()) else {
+            continue;
+        };
+
+        if jpn.contains_key(&from) {
+            if let Some(text) = eng.get(&to) {
+                translations.entry(from).or_default().push(text.clone());
+            }
+        }
+    }
+
+    let mut entries: Vec<TatoebaEntry> = jpn
+        .into_iter()
+        .map(|(id, text)| TatoebaEntry {
+            id,
+            translations: translations.remove(&id).unwrap_or_default(),
+            text,
+        })
+        .collect();
+
+    // same order as Immersion Kit's `sort=shortness`
+    entries.sort_by(|a, b| {
+        a.text
+            .chars()
+            .count()
+            .cmp(&b.text.chars().count())
+            .then(a.id.cmp(&b.id))
+    });
+
+    Ok(entries)
+}
@@ -0,0 +1,275 @@
+use crate::anki::{UpdateNotesRes, UpdatePlan};
+use crate::app::*;
+use crate::jmdict::Dictionary;
+use crate::journal::JournalBatch;
+use crate::known::KnownWords;
+use crate::sources::SourceResult;
+use crate::tokenizer::{Token, Tokenizer};
+use crate::yomitan::YomitanStore;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+const SPINNER: [&str; 8] = ["⣾", "⣽", "⣻", "⢿", "⡿", "⣟", "⣯", "⣷"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskKind {
+    Sentences,
+    Audio,
+    UpdateCards,
+    KnownWords,
+    Dictionary,
+    Yomitan,
+    Tokenizer,
+}
+
+/// Sent back to the event loop when a background task finishes.
+pub enum TaskResult {
+    Sentences {
+        dict_word: String,
+        elapsed: Duration,
+        result: Result<(SourceResult, &'static str), String>,
+        prefetched: bool,
+    },
+    Audio {
+        dict_word: String,
+        sentence: String,
+        /// Only set when the audio had to be downloaded.
+        audio_data: Option<Vec<u8>>,
+        result: Result<(), String>,
+    },
+    UpdateCards {
+        elapsed: Duration,
+        res: UpdateNotesRes,
+    },
+    /// An update to preview before it's committed.
+    PlanUpdate {
+        plan: Box<UpdatePlan>,
+    },
+    /// Each note's result of undoing the batch.
+    Undo {
+        batch: JournalBatch,
+        results: Vec<Result<(), String>>,
+    },
+    KnownWords {
+        result: Result<KnownWords, String>,
+    },
+    Dictionary {
+        result: Result<Option<Dictionary>, String>,
+    },
+    Yomitan {
+        /// The loaded dictionaries & the errors of the ones that failed.
+        result: Result<(YomitanStore, Vec<String>), String>,
+    },
+    Tokenizer {
+        result: Result<Box<Tokenizer>, String>,
+    },
+    /// The tokens of the sentences fetched before the tokenizer loaded, by expression & text.
+    Tokens {
+        tokens: HashMap<(String, String), Vec<Token>>,
+    },
+}
+
+struct Task {
+    id: u64,
+    kind: TaskKind,
+    dict_word: Option<String>,
+    handle: JoinHandle<()>,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Task {
+    fn cancel(&self) {
+        if let Some(stop) = &self.stop {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.handle.abort();
+    }
+}
+
+pub struct TaskQueue {
+    tx: UnboundedSender<(u64, TaskResult)>,
+    rx: UnboundedReceiver<(u64, TaskResult)>,
+    tasks: Vec<Task>,
+    next_id: u64,
+    created: Instant,
+}
+
+impl Default for TaskQueue {
+    fn default() -> Self {
+        let (tx, rx) = unbounded_channel();
+        Self {
+            tx,
+            rx,
+            tasks: Vec::new(),
+            next_id: 0,
+            created: Instant::now(),
+        }
+    }
+}
+
+impl TaskQueue {
+    pub fn spawn<F>(&mut self, kind: TaskKind, dict_word: Option<String>, fut: F)
+    where
+        F: Future<Output = TaskResult> + Send + 'static,
+    {
+        self.spawn_with_stop(kind, dict_word, None, fut);
+    }
+
+    /// Same as `spawn`, `stop` is set when the task is cancelled,
+    /// for work that can't be aborted (ie. blocking audio playback).
+    pub fn spawn_with_stop<F>(
+        &mut self,
+        kind: TaskKind,
+        dict_word: Option<String>,
+        stop: Option<Arc<AtomicBool>>,
+        fut: F,
+    ) where
+        F: Future<Output = TaskResult> + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let tx = self.tx.clone();
+        let handle = tokio::spawn(async move {
+            let res = fut.await;
+            let _ = tx.send((id, res));
+        });
+
+        self.tasks.push(Task {
+            id,
+            kind,
+            dict_word,
+            handle,
+            stop,
+        });
+    }
+
+    /// A task can finish before it's cancelled, its result is dropped once it's not queued.
+    pub fn try_recv(&mut self) -> Option<TaskResult> {
+        loop {
+            let (id, res) = self.rx.try_recv().ok()?;
+            let before = self.tasks.len();
+            self.tasks.retain(|task| task.id != id);
+            if self.tasks.len() < before {
+                return Some(res);
+            }
+        }
+    }
+
+    pub fn is_running(&self, kind: TaskKind) -> bool {
+        self.tasks.iter().any(|task| task.kind == kind)
+    }
+
+    /// Expressions that are currently fetching sentences.
+    pub fn loading_words(&self) -> Vec<String> {
+        self.tasks
+            .iter()
+            .filter(|task| task.kind == TaskKind::Sentences)
+            .filter_map(|task| task.dict_word.clone())
+            .collect()
+    }
+
+    /// Cancels every task of `kind`, or only the ones for `dict_word` if given.
+    /// Returns the number of cancelled tasks.
+    pub fn cancel(&mut self, kind: TaskKind, dict_word: Option<&str>) -> usize {
+        let before = self.tasks.len();
+        self.tasks.retain(|task| {
+            let matches = task.kind == kind
+                && dict_word.is_none_or(|word| task.dict_word.as_deref() == Some(word));
+            if matches {
+                task.cancel();
+            }
+            !matches
+        });
+        before - self.tasks.len()
+    }
+
+    pub fn spinner(&self) -> &'static str {
+        let frame = self.created.elapsed().as_millis() / 100;
+        SPINNER[frame as usize % SPINNER.len()]
+    }
+}
+
+impl AppState {
+    /// Applies the results of any finished background tasks.
+    pub fn handle_task_results(&mut self) {
+        while let Some(res) = self.tasks.try_recv() {
+            match res {
+                TaskResult::Sentences {
+                    dict_word,
+                    elapsed,
+                    result,
+                    prefetched,
+                } => self.apply_fetched_sentences(&dict_word, elapsed, result, prefetched),
+                TaskResult::Audio {
+                    dict_word,
+                    sentence,
+                    audio_data,
+                    result,
+                } => {
+                    if let Some(audio_data) = audio_data {
+                        self.store_audio_data(&dict_word, &sentence, audio_data);
+                    }
+                    if let Err(err) = result {
+                        self.update_error_msg("Error Playing Audio", err);
+                    }
+                }
+                TaskResult::UpdateCards { elapsed, res } => self.finish_update_cards(elapsed, res),
+                TaskResult::PlanUpdate { plan } => self.open_update_preview(*plan),
+                TaskResult::Undo { batch, results } => self.finish_undo(batch, results),
+                TaskResult::KnownWords { result } => match result {
+                    Ok(known) => self.set_known_words(known),
+                    Err(err) => self.update_error_msg("Error Loading Known Words", err),
+                },
+                TaskResult::Dictionary { result } => match result {
+                    Ok(dict) => self.set_dictionary(dict),
+                    Err(err) => self.update_error_msg("Error Loading JMdict", err),
+                },
+                TaskResult::Yomitan { result } => match result {
+                    Ok((store, errors)) => self.set_yomitan(store, errors),
+                    Err(err) => self.update_error_msg("Error Loading Yomitan", err),
+                },
+                TaskResult::Tokenizer { result } => match result {
+                    Ok(tokenizer) => self.set_tokenizer(tokenizer),
+                    Err(err) => self.update_error_msg("Error Loading Tokenizer", err),
+                },
+                TaskResult::Tokens { tokens } => self.set_sentence_tokens(tokens),
+            }
+        }
+    }
+
+    fn store_audio_data(&mut self, dict_word: &str, sentence: &str, audio_data: Vec<u8>) {
+        let sentences = self
+            .expressions
+            .iter_mut()
+            .filter(|exp| exp.dict_word == dict_word)
+            .filter_map(|exp| exp.sentences.as_mut());
+
+        for sentences in sentences {
+            if let Some(sent) = sentences.iter_mut().find(|s| s.sentence == sentence) {
+                sent.audio_data = Some(audio_data.clone());
+            }
+        }
+    }
+
+    /// Cancels the selected expression's sentence fetch & any playing audio.
+    pub fn cancel_selected_tasks(&mut self) {
+        let mut cancelled = self.tasks.cancel(TaskKind::Audio, None);
+
+        if let Some(i) = self.selected_expression {
+            if let Some(exp) = self.expressions.get(i) {
+                let dict_word = exp.dict_word.clone();
+                cancelled += self.tasks.cancel(TaskKind::Sentences, Some(&dict_word));
+            }
+        }
+
+        if cancelled > 0 {
+            self.info.msg = Some(format!("Cancelled {} Task(s)", cancelled));
+        }
+    }
+}
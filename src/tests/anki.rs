@@ -0,0 +1,748 @@
+use super::mock_anki::{MockAnki, EXPRESSION};
+use crate::anki::*;
+use crate::app::{Expression, Sentence};
+use crate::cache::CacheConfig;
+use crate::journal::{mark_undone, read_journal, JournalConfig};
+use anki_direct::AnkiClient as AnkiDirectClient;
+
+fn config(anki: &MockAnki) -> ConfigJson {
+    ConfigJson {
+        fields: UserNoteFields {
+            expression: EXPRESSION.to_string(),
+            sentence: "Sentence".to_string(),
+            sentence_audio: "Audio".to_string(),
+            image: "Picture".to_string(),
+            reading: FieldMapping::Name("Reading".to_string()),
+            definition: FieldMapping::Detailed {
+                field: "Definition".to_string(),
+                policy: FieldPolicy::IfEmpty,
+            },
+            ..Default::default()
+        },
+        anki_connect: AnkiConnectConfig {
+            url: anki.url(),
+            ..Default::default()
+        },
+        cache: CacheConfig {
+            enabled: false,
+            ..Default::default()
+        },
+        journal: JournalConfig {
+            enabled: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// A journal in the temp dir, that doesn't exist yet.
+fn journal(name: &str) -> JournalConfig {
+    let path =
+        std::env::temp_dir().join(format!("anki_helper_{}_{}.json", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    JournalConfig {
+        enabled: true,
+        path: path.to_string_lossy().to_string(),
+        ..Default::default()
+    }
+}
+
+fn sentence(word: &str, note_id: Option<u128>, text: &str, audio_url: Option<&str>) -> Sentence {
+    let mut exp = Expression::from(word.to_string(), None, None, note_id);
+    exp.readings = vec!["たべる".to_string()];
+    exp.definitions = vec!["to eat".to_string()];
+    Sentence::from(text, audio_url.map(String::from), None, None, "", "", &exp)
+}
+
+/// The ids of the notes `find_notes` matched.
+fn note_ids(found: Vec<Result<NoteInfo, NoteOutcome>>) -> Vec<Result<u128, NoteOutcome>> {
+    found
+        .into_iter()
+        .map(|found| found.map(|info| info.noteId))
+        .collect()
+}
+
+fn outcomes(res: &UpdateNotesRes) -> Vec<NoteOutcome> {
+    res.results.iter().map(|r| r.outcome.clone()).collect()
+}
+
+#[tokio::test]
+async fn updates_note_with_known_id() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let audio_url = anki.serve_file("/audio/taberu.mp3", b"taberu audio");
+    let sentences = vec![sentence(
+        "食べる",
+        Some(id),
+        "パンを食べる。",
+        Some(&audio_url),
+    )];
+
+    let res = update_anki_cards(sentences, &config(&anki)).await;
+
+    assert_eq!(outcomes(&res), vec![NoteOutcome::Updated(id)]);
+    assert_eq!(res.success_len(), 1);
+    // the id is known, so the note isn't searched for
+    assert!(anki.requests("findNotes").is_empty());
+    let note = anki.note(id).unwrap();
+    assert_eq!(note.field("Sentence"), Some("パンを食べる。"));
+    assert_eq!(note.field("Reading"), Some("たべる"));
+    assert_eq!(note.field("Definition"), Some("to eat"));
+
+    let media = anki.media();
+    assert_eq!(media.len(), 1);
+    assert!(media[0].starts_with("taberu_") && media[0].ends_with(".mp3"));
+    assert_eq!(anki.media_file(&media[0]).unwrap(), b"taberu audio");
+    assert_eq!(
+        note.field("Audio"),
+        Some(format!("[sound:{}]", media[0]).as_str())
+    );
+}
+
+#[tokio::test]
+async fn stores_audio_data_and_images() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let img_url = anki.serve_file("/images/pan.jpg", b"pan image");
+    let exp = Expression::from("食べる".to_string(), None, None, Some(id));
+    let clip = b"subtitle clip".to_vec();
+    let sentence = Sentence::from(
+        "パンを食べる。",
+        None,
+        Some(clip),
+        Some(img_url),
+        "Show",
+        "",
+        &exp,
+    );
+
+    let res = update_anki_cards(vec![sentence], &config(&anki)).await;
+
+    assert_eq!(outcomes(&res), vec![NoteOutcome::Updated(id)]);
+    let media = anki.media();
+    assert_eq!(media.len(), 2);
+    assert_eq!(anki.media_file(&media[0]).unwrap(), b"subtitle clip");
+    assert!(media[1].starts_with("pan_") && media[1].ends_with(".jpg"));
+    let note = anki.note(id).unwrap();
+    assert_eq!(
+        note.field("Audio"),
+        Some(format!("[sound:{}]", media[0]).as_str())
+    );
+    assert_eq!(
+        note.field("Picture"),
+        Some(format!("<img src=\"{}\">", media[1]).as_str())
+    );
+    // the files are sent as base64, not as urls for Anki to download
+    let uploads = anki.requests("storeMediaFile");
+    assert!(uploads.iter().all(|params| params.get("url").is_none()));
+    assert!(uploads
+        .iter()
+        .all(|params| params["deleteExisting"] == false));
+}
+
+#[tokio::test]
+async fn same_named_media_from_different_decks_dont_collide() {
+    let anki = MockAnki::start().await;
+    let first = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let second = anki.add_note(&[(EXPRESSION, "飲む")]);
+    let anime = anki.serve_file("/anime/sentence_1.mp3", b"anime audio");
+    let drama = anki.serve_file("/drama/sentence_1.mp3", b"drama audio");
+    let sentences = vec![
+        sentence("食べる", Some(first), "パンを食べる。", Some(&anime)),
+        sentence("飲む", Some(second), "水を飲む。", Some(&drama)),
+    ];
+
+    let res = update_anki_cards(sentences, &config(&anki)).await;
+
+    assert_eq!(res.success_len(), 2);
+    let media = anki.media();
+    assert_eq!(media.len(), 2);
+    assert_ne!(media[0], media[1]);
+    for (id, bytes) in [(first, b"anime audio"), (second, b"drama audio")] {
+        let note = anki.note(id).unwrap();
+        let audio = note.field("Audio").unwrap();
+        let name = audio.trim_start_matches("[sound:").trim_end_matches(']');
+        assert_eq!(anki.media_file(name).unwrap(), bytes);
+    }
+}
+
+#[tokio::test]
+async fn media_errors_still_update_the_sentence() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let missing = format!("{}/audio/missing.mp3", anki.url());
+    let sentences = vec![sentence(
+        "食べる",
+        Some(id),
+        "パンを食べる。",
+        Some(&missing),
+    )];
+
+    let res = update_anki_cards(sentences, &config(&anki)).await;
+
+    match &res.results[0].outcome {
+        NoteOutcome::MediaFailed(note_id, err) => {
+            assert_eq!(*note_id, id);
+            assert!(err.starts_with("Err Downloading Media For 食べる"));
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+    assert_eq!(res.success_len(), 0);
+    let note = anki.note(id).unwrap();
+    assert_eq!(note.field("Sentence"), Some("パンを食べる。"));
+    assert_eq!(note.field("Audio"), Some(""));
+}
+
+#[tokio::test]
+async fn finds_note_for_inflected_word_and_keeps_if_empty_fields() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる"), ("Definition", "to eat (food)")]);
+    // the word as it was typed, without a dictionary to normalize it
+    let sentences = vec![sentence("食べた", None, "もう食べた。", None)];
+    let config = config(&anki);
+
+    let res = update_anki_cards(sentences, &config).await;
+
+    assert_eq!(outcomes(&res), vec![NoteOutcome::Updated(id)]);
+    assert_eq!(
+        anki.requests("findNotes")[0]["query"],
+        expression_query(&config, "食べた")
+    );
+    let updates = anki.requests("updateNoteFields");
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0]["note"]["id"], id as u64);
+    assert!(updates[0]["note"]["fields"].get("Definition").is_none());
+    let note = anki.note(id).unwrap();
+    assert_eq!(note.field("Sentence"), Some("もう食べた。"));
+    assert_eq!(note.field("Definition"), Some("to eat (food)"));
+}
+
+#[tokio::test]
+async fn uses_the_note_id_picked_for_the_sentence() {
+    let anki = MockAnki::start().await;
+    anki.add_note(&[(EXPRESSION, "行く")]);
+    let picked = anki.add_note(&[(EXPRESSION, "行く")]);
+    let mut sentence = sentence("行く", None, "学校に行った。", None);
+    sentence.note_id = Some(picked);
+
+    let res = update_anki_cards(vec![sentence], &config(&anki)).await;
+
+    assert_eq!(outcomes(&res), vec![NoteOutcome::Updated(picked)]);
+    assert!(anki.requests("findNotes").is_empty());
+}
+
+#[tokio::test]
+async fn reports_an_outcome_per_sentence() {
+    let anki = MockAnki::start().await;
+    let eat = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let go = [
+        anki.add_note(&[(EXPRESSION, "行く")]),
+        anki.add_note(&[(EXPRESSION, "<b>行く</b>")]),
+    ];
+    let drink = anki.add_other_note(&[(EXPRESSION, "飲む"), ("Sentence", "")]);
+    let deleted = 42;
+    let sentences = vec![
+        sentence("食べる", None, "パンを食べる。", None),
+        sentence("行く", None, "学校に行く。", None),
+        sentence("見る", None, "映画を見る。", None),
+        sentence("飲む", Some(drink), "水を飲む。", None),
+        sentence("寝る", Some(deleted), "もう寝る。", None),
+    ];
+
+    let res = update_anki_cards(sentences, &config(&anki)).await;
+
+    assert_eq!(
+        outcomes(&res),
+        vec![
+            NoteOutcome::Updated(eat),
+            NoteOutcome::Ambiguous(go.to_vec()),
+            NoteOutcome::NoNote,
+            NoteOutcome::FieldMissing("Definition".to_string()),
+            NoteOutcome::NoNote,
+        ]
+    );
+    let words: Vec<&str> = res
+        .results
+        .iter()
+        .map(|r| r.sentence.parent_expression.dict_word.as_str())
+        .collect();
+    assert_eq!(words, vec!["食べる", "行く", "見る", "飲む", "寝る"]);
+    // only the note that matched is written to
+    let updates = anki.requests("updateNoteFields");
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0]["note"]["id"], eat as u64);
+    assert!(anki.requests("addNote").is_empty());
+}
+
+#[tokio::test]
+async fn adds_notes_for_missing_words() {
+    let anki = MockAnki::start().await;
+    let mut config = config(&anki);
+    config.new_notes.enabled = true;
+    config.new_notes.deck = "Mining".to_string();
+    let sentences = vec![sentence("食べる", None, "パンを食べる。", None)];
+
+    let res = update_anki_cards(sentences, &config).await;
+
+    assert_eq!(res.created_len(), 1);
+    assert_eq!(res.success_len(), 1);
+    let NoteOutcome::Created(id) = res.results[0].outcome else {
+        panic!("unexpected outcome: {:?}", res.results[0].outcome);
+    };
+    let note = anki.note(id).unwrap();
+    assert_eq!(note.deck, "Mining");
+    assert_eq!(note.tags, vec!["anki_helper".to_string()]);
+    assert_eq!(note.field(EXPRESSION), Some("食べる"));
+    assert_eq!(note.field("Sentence"), Some("パンを食べる。"));
+    // a new note has nothing to keep, so `if_empty` fields are filled
+    assert_eq!(note.field("Definition"), Some("to eat"));
+    assert!(anki.requests("updateNoteFields").is_empty());
+}
+
+#[tokio::test]
+async fn reports_add_note_errors_per_word() {
+    let anki = MockAnki::start().await;
+    anki.fail("addNote", "deck was not found: Mining");
+    let mut config = config(&anki);
+    config.new_notes.enabled = true;
+    let sentences = vec![sentence("食べる", None, "パンを食べる。", None)];
+
+    let res = update_anki_cards(sentences, &config).await;
+
+    assert_eq!(
+        outcomes(&res),
+        vec![NoteOutcome::Failed(
+            "deck was not found: Mining".to_string()
+        )]
+    );
+}
+
+#[tokio::test]
+async fn doesnt_add_notes_when_new_notes_are_off() {
+    let anki = MockAnki::start().await;
+    let sentences = vec![sentence("食べる", None, "パンを食べる。", None)];
+
+    let res = update_anki_cards(sentences, &config(&anki)).await;
+
+    assert_eq!(outcomes(&res), vec![NoteOutcome::NoNote]);
+    assert!(anki.requests("addNote").is_empty());
+}
+
+#[tokio::test]
+async fn reports_every_update_error() {
+    let anki = MockAnki::start().await;
+    let first = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let second = anki.add_note(&[(EXPRESSION, "飲む")]);
+    anki.fail("updateNoteFields", "collection is not available");
+    let sentences = vec![
+        sentence("食べる", Some(first), "パンを食べる。", None),
+        sentence("飲む", Some(second), "水を飲む。", None),
+    ];
+
+    let res = update_anki_cards(sentences, &config(&anki)).await;
+
+    let failed = NoteOutcome::Failed("collection is not available".to_string());
+    assert_eq!(outcomes(&res), vec![failed.clone(), failed]);
+    assert_eq!(anki.requests("updateNoteFields").len(), 2);
+}
+
+#[tokio::test]
+async fn returns_new_anki_words() {
+    let anki = MockAnki::start().await;
+    let plain = anki.add_note(&[(EXPRESSION, " 食べる ")]);
+    let html = anki.add_note(&[(EXPRESSION, "<span>飲む</span>")]);
+    let client = AnkiDirectClient::default();
+
+    let words = return_new_anki_words(&client, &config(&anki), "is:new")
+        .await
+        .unwrap();
+
+    let words: Vec<(String, Option<u128>)> = words
+        .into_iter()
+        .map(|exp| (exp.dict_word, exp.note_id))
+        .collect();
+    assert_eq!(
+        words,
+        vec![
+            ("食べる".to_string(), Some(plain)),
+            ("飲む".to_string(), Some(html)),
+        ]
+    );
+    assert_eq!(anki.requests("findNotes")[0]["query"], "is:new");
+}
+
+#[tokio::test]
+async fn return_new_anki_words_checks_the_expression_field() {
+    let anki = MockAnki::start().await;
+    anki.add_other_note(&[("Word", "食べる")]);
+    let client = AnkiDirectClient::default();
+
+    let err = return_new_anki_words(&client, &config(&anki), "is:new")
+        .await
+        .err()
+        .unwrap();
+
+    assert!(err.to_string().starts_with("Incorrect Field: `Expression`"));
+}
+
+#[tokio::test]
+async fn return_new_anki_words_surfaces_errors() {
+    let anki = MockAnki::start().await;
+    anki.add_note(&[(EXPRESSION, "食べる")]);
+    anki.fail("notesInfo", "collection is not available");
+    let client = AnkiDirectClient::default();
+
+    let err = return_new_anki_words(&client, &config(&anki), "is:new")
+        .await
+        .err()
+        .unwrap();
+
+    assert_eq!(err.to_string(), "collection is not available");
+}
+
+#[tokio::test]
+async fn batches_lookups_and_updates() {
+    let anki = MockAnki::start().await;
+    let words = ["食べる", "飲む", "見る", "寝る", "行く"];
+    let ids: Vec<u128> = words
+        .iter()
+        .map(|word| anki.add_note(&[(EXPRESSION, word)]))
+        .collect();
+    let mut config = config(&anki);
+    config.anki_connect.batch_size = 2;
+    let sentences = words
+        .iter()
+        .map(|word| sentence(word, None, &format!("{}文。", word), None))
+        .collect();
+
+    let res = update_anki_cards(sentences, &config).await;
+
+    let updated: Vec<NoteOutcome> = ids.iter().map(|id| NoteOutcome::Updated(*id)).collect();
+    assert_eq!(outcomes(&res), updated);
+    // 3 `multi` searches, 1 `notesInfo` while planning & 1 before the 3 `multi` updates
+    assert_eq!(anki.round_trips(), 8);
+    let batches: Vec<usize> = anki
+        .requests("multi")
+        .iter()
+        .map(|params| params["actions"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(batches, vec![2, 2, 1, 2, 2, 1]);
+    assert_eq!(anki.requests("findNotes").len(), 5);
+    assert_eq!(anki.requests("updateNoteFields").len(), 5);
+}
+
+#[tokio::test]
+async fn a_failed_batch_fails_all_of_its_notes() {
+    let anki = MockAnki::start().await;
+    let first = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let second = anki.add_note(&[(EXPRESSION, "飲む")]);
+    anki.fail("multi", "collection is not available");
+    let sentences = vec![
+        sentence("食べる", Some(first), "パンを食べる。", None),
+        sentence("飲む", Some(second), "水を飲む。", None),
+        sentence("見る", None, "映画を見る。", None),
+    ];
+
+    let res = update_anki_cards(sentences, &config(&anki)).await;
+
+    let failed = NoteOutcome::Failed("collection is not available".to_string());
+    assert_eq!(outcomes(&res), vec![failed.clone(), failed.clone(), failed]);
+    assert!(anki.requests("updateNoteFields").is_empty());
+}
+
+#[tokio::test]
+async fn plans_the_changes_without_writing_them() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[
+        (EXPRESSION, "食べる"),
+        ("Sentence", "古い文。"),
+        ("Definition", "to eat (food)"),
+    ]);
+    let audio_url = anki.serve_file("/audio/taberu.mp3", b"taberu audio");
+    let sentences = vec![sentence(
+        "食べる",
+        Some(id),
+        "パンを食べる。",
+        Some(&audio_url),
+    )];
+
+    let plan = plan_note_updates(sentences, &config(&anki)).await;
+
+    let note = &plan.notes[0];
+    assert_eq!(note.action, NoteAction::Update(id));
+    let changes: Vec<(&str, Option<&str>, &str)> = note
+        .changes
+        .iter()
+        .map(|c| (c.field.as_str(), c.current.as_deref(), c.new.as_str()))
+        .collect();
+    let audio = note.changes[1].new.clone();
+    assert!(audio.starts_with("[sound:taberu_") && audio.ends_with(".mp3]"));
+    // `Definition` isn't empty, so it's kept
+    assert_eq!(
+        changes,
+        vec![
+            ("Sentence", Some("古い文。"), "パンを食べる。"),
+            ("Audio", Some(""), audio.as_str()),
+            ("Reading", Some(""), "たべる"),
+        ]
+    );
+    assert!(note.changes.iter().all(|change| change.is_changed()));
+    assert!(anki.requests("updateNoteFields").is_empty());
+    assert!(anki.media().is_empty());
+    assert_eq!(anki.note(id).unwrap().field("Sentence"), Some("古い文。"));
+
+    let res = commit_note_updates(plan).await;
+
+    assert_eq!(outcomes(&res), vec![NoteOutcome::Updated(id)]);
+    let note = anki.note(id).unwrap();
+    assert_eq!(note.field("Sentence"), Some("パンを食べる。"));
+    assert_eq!(note.field("Audio"), Some(audio.as_str()));
+    assert_eq!(anki.media().len(), 1);
+}
+
+#[tokio::test]
+async fn commits_only_the_approved_notes() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる"), ("Sentence", "古い文。")]);
+    let other = anki.add_other_note(&[(EXPRESSION, "見る"), ("Sentence", "")]);
+    let mut config = config(&anki);
+    config.new_notes.enabled = true;
+    let sentences = vec![
+        sentence("食べる", Some(id), "パンを食べる。", None),
+        sentence("飲む", None, "水を飲む。", None),
+        sentence("見る", Some(other), "映画を見る。", None),
+    ];
+
+    let mut plan = plan_note_updates(sentences, &config).await;
+
+    let actions: Vec<&NoteAction> = plan.notes.iter().map(|note| &note.action).collect();
+    assert_eq!(
+        actions,
+        vec![
+            &NoteAction::Update(id),
+            &NoteAction::Add,
+            &NoteAction::Skip(NoteOutcome::FieldMissing("Definition".to_string())),
+        ]
+    );
+    let added = &plan.notes[1].changes;
+    assert_eq!(added[0].field, EXPRESSION);
+    assert_eq!(added[0].new, "飲む");
+    assert!(added.iter().all(|change| change.current.is_none()));
+    assert!(plan.notes[2].changes.is_empty());
+
+    plan.notes[0].approved = false;
+    let unapproved = plan.take_unapproved();
+    assert_eq!(unapproved.len(), 1);
+    assert_eq!(unapproved[0].sentence, "パンを食べる。");
+
+    let res = commit_note_updates(plan).await;
+
+    let words: Vec<&str> = res
+        .results
+        .iter()
+        .map(|r| r.sentence.parent_expression.dict_word.as_str())
+        .collect();
+    assert_eq!(words, vec!["飲む", "見る"]);
+    assert_eq!(res.created_len(), 1);
+    let skipped = NoteOutcome::FieldMissing("Definition".to_string());
+    assert_eq!(res.results[1].outcome, skipped);
+    assert!(anki.requests("updateNoteFields").is_empty());
+    assert_eq!(anki.note(id).unwrap().field("Sentence"), Some("古い文。"));
+}
+
+#[tokio::test]
+async fn journals_the_fields_and_undoes_the_update() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる"), ("Sentence", "古い文。")]);
+    let added = anki.add_note(&[(EXPRESSION, "飲む")]);
+    let mut config = config(&anki);
+    config.journal = journal("undo");
+    let sentences = vec![
+        sentence("食べる", Some(id), "パンを食べる。", None),
+        sentence("飲む", Some(added), "水を飲む。", None),
+    ];
+
+    let res = update_anki_cards(sentences, &config).await;
+
+    assert_eq!(res.success_len(), 2);
+    let batches = read_journal(&config.journal).unwrap();
+    assert_eq!(batches.len(), 1);
+    let snapshot = &batches[0].notes[0];
+    assert_eq!((snapshot.id, snapshot.dict_word.as_str()), (id, "食べる"));
+    let mut fields: Vec<(&str, &str)> = snapshot
+        .fields
+        .iter()
+        .map(|(field, value)| (field.as_str(), value.as_str()))
+        .collect();
+    fields.sort();
+    assert_eq!(
+        fields,
+        vec![
+            ("Definition", ""),
+            ("Reading", ""),
+            ("Sentence", "古い文。")
+        ]
+    );
+    assert_eq!(anki.note(id).unwrap().field("Reading"), Some("たべる"));
+
+    let results = undo_batch(&batches[0], &config).await;
+
+    assert_eq!(results, vec![Ok(()), Ok(())]);
+    let note = anki.note(id).unwrap();
+    assert_eq!(note.field("Sentence"), Some("古い文。"));
+    assert_eq!(note.field("Reading"), Some(""));
+    assert_eq!(note.field("Definition"), Some(""));
+    assert_eq!(anki.note(added).unwrap().field("Sentence"), Some(""));
+
+    mark_undone(&config.journal, batches[0].id).unwrap();
+    assert!(read_journal(&config.journal).unwrap()[0].undone);
+    let _ = std::fs::remove_file(&config.journal.path);
+}
+
+#[tokio::test]
+async fn journals_the_fields_the_notes_have_when_committed() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる"), ("Sentence", "古い文。")]);
+    let mut config = config(&anki);
+    config.journal = journal("edited");
+    let sentences = vec![sentence("食べる", Some(id), "パンを食べる。", None)];
+
+    let plan = plan_note_updates(sentences, &config).await;
+    // edited in Anki while the preview was open
+    anki.edit_note(id, "Sentence", "直した文。");
+    anki.edit_note(id, "Definition", "to eat (food)");
+    let res = commit_note_updates(plan).await;
+
+    assert_eq!(outcomes(&res), vec![NoteOutcome::Updated(id)]);
+    let batches = read_journal(&config.journal).unwrap();
+    let fields = &batches[0].notes[0].fields;
+    assert_eq!(fields["Sentence"], "直した文。");
+    // the `if_empty` definition isn't overwritten, so it's not journaled either
+    assert!(!fields.contains_key("Definition"));
+    assert_eq!(
+        anki.note(id).unwrap().field("Definition"),
+        Some("to eat (food)")
+    );
+    let _ = std::fs::remove_file(&config.journal.path);
+}
+
+#[tokio::test]
+async fn undo_deletes_the_added_notes() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let mut config = config(&anki);
+    config.journal = journal("added");
+    config.new_notes.enabled = true;
+    let sentences = vec![
+        sentence("食べる", Some(id), "パンを食べる。", None),
+        sentence("飲む", None, "水を飲む。", None),
+    ];
+
+    let res = update_anki_cards(sentences, &config).await;
+
+    let NoteOutcome::Created(added) = res.results[1].outcome else {
+        panic!("unexpected outcome: {:?}", res.results[1].outcome);
+    };
+    let batches = read_journal(&config.journal).unwrap();
+    assert_eq!(batches[0].created, vec![added]);
+
+    let results = undo_batch(&batches[0], &config).await;
+
+    assert_eq!(results, vec![Ok(()), Ok(())]);
+    assert!(anki.note(added).is_none());
+    assert_eq!(anki.note(id).unwrap().field("Sentence"), Some(""));
+    let _ = std::fs::remove_file(&config.journal.path);
+}
+
+#[tokio::test]
+async fn doesnt_update_notes_it_cant_journal() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "食べる")]);
+    let mut config = config(&anki);
+    // the journal's directory is a file, so it can't be written
+    config.journal = journal("blocker");
+    let blocker = config.journal.path.clone();
+    std::fs::write(&blocker, b"").unwrap();
+    config.journal.path = format!("{}/journal.json", blocker);
+    let sentences = vec![sentence("食べる", Some(id), "パンを食べる。", None)];
+
+    let res = update_anki_cards(sentences, &config).await;
+
+    match &res.results[0].outcome {
+        NoteOutcome::Failed(err) => assert!(err.starts_with("Err Writing Undo Journal")),
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+    assert!(anki.requests("updateNoteFields").is_empty());
+    let _ = std::fs::remove_file(&blocker);
+}
+
+#[tokio::test]
+async fn find_notes_matches_the_expression_field() {
+    let anki = MockAnki::start().await;
+    let id = anki.add_note(&[(EXPRESSION, "<b>食べる</b>")]);
+    // found by the search, but the expression is a different word
+    anki.add_note(&[(EXPRESSION, "食べ物"), ("Sentence", "食べる")]);
+    let client = reqwest::Client::new();
+    let config = config(&anki);
+
+    let words = ["食べる", "食べた", "食べなかった", "飲む"].map(String::from);
+    assert_eq!(
+        note_ids(find_notes(&client, &config, &words).await),
+        vec![Ok(id), Ok(id), Ok(id), Err(NoteOutcome::NoNote)]
+    );
+}
+
+#[test]
+fn searches_the_expression_field_for_each_dictionary_form() {
+    let config = ConfigJson {
+        fields: UserNoteFields {
+            expression: "Word \"Kanji\"".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let query = expression_query(&config, " 食べた ");
+
+    assert!(query.starts_with(r#"("Word \"Kanji\":*食べた*" OR "#));
+    assert!(query.contains(r#" OR "Word \"Kanji\":*食べる*""#));
+    assert!(query.ends_with(')'));
+    assert_eq!(
+        expression_query(&config, "a*_b"),
+        r#"("Word \"Kanji\":*a\*\_b*")"#
+    );
+}
+
+#[tokio::test]
+async fn find_notes_reports_ambiguous_matches_and_skips_other_note_types() {
+    let anki = MockAnki::start().await;
+    let go = [
+        anki.add_note(&[(EXPRESSION, "行く")]),
+        anki.add_note(&[(EXPRESSION, "<span>行く</span>")]),
+    ];
+    anki.add_other_note(&[("Word", "飲む")]);
+    let client = reqwest::Client::new();
+    let config = config(&anki);
+
+    let words = ["行く", "飲む"].map(String::from);
+    let found = note_ids(find_notes(&client, &config, &words).await);
+
+    assert_eq!(found[0], Err(NoteOutcome::Ambiguous(go.to_vec())));
+    // only the expression field is searched
+    assert_eq!(found[1], Err(NoteOutcome::NoNote));
+}
+
+#[tokio::test]
+async fn find_notes_surfaces_errors() {
+    let anki = MockAnki::start().await;
+    anki.fail("findNotes", "collection is not available");
+    let client = reqwest::Client::new();
+    let words = ["食べる".to_string()];
+
+    assert_eq!(
+        note_ids(find_notes(&client, &config(&anki), &words).await)[0],
+        Err(NoteOutcome::Failed(
+            "collection is not available".to_string()
+        ))
+    );
+}
@@ -0,0 +1,108 @@
+use super::mock_anki::MockAnki;
+use crate::cache::*;
+use std::time::{Duration, SystemTime};
+
+fn cache(name: &str) -> (Cache, String) {
+    let dir =
+        std::env::temp_dir().join(format!("anki_helper_cache_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_string_lossy().to_string();
+    let config = CacheConfig {
+        dir: dir.clone(),
+        ..Default::default()
+    };
+    (Cache::new(&config), dir)
+}
+
+fn is_json(bytes: &[u8]) -> bool {
+    serde_json::from_slice::<serde_json::Value>(bytes).is_ok()
+}
+
+#[tokio::test]
+async fn doesnt_cache_invalid_responses() {
+    let server = MockAnki::start().await;
+    let (cache, dir) = cache("invalid");
+    let url = server.serve_file("/search", b"<html>Bad Gateway</html>");
+
+    let bytes = cache
+        .fetch_bytes_with("search", "word", &url, is_json)
+        .await
+        .unwrap();
+
+    assert_eq!(bytes, b"<html>Bad Gateway</html>");
+    assert!(cache.get("search", "word").await.is_none());
+
+    server.serve_file("/search", b"{\"results\": []}");
+    let bytes = cache
+        .fetch_bytes_with("search", "word", &url, is_json)
+        .await
+        .unwrap();
+
+    assert_eq!(bytes, b"{\"results\": []}");
+    assert_eq!(cache.get("search", "word").await, Some(bytes));
+
+    // an expired entry is still kept over a later error page
+    let entry = std::fs::read_dir(std::path::Path::new(&dir).join("search"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let expired =
+        SystemTime::now() - Duration::from_secs(CacheConfig::default().ttl_hours * 3600 + 60);
+    std::fs::File::options()
+        .write(true)
+        .open(&entry)
+        .unwrap()
+        .set_modified(expired)
+        .unwrap();
+    assert!(cache.get("search", "word").await.is_none());
+
+    server.serve_file("/search", b"<html>Bad Gateway</html>");
+    let bytes = cache
+        .fetch_bytes_with("search", "word", &url, is_json)
+        .await
+        .unwrap();
+    assert_eq!(bytes, b"{\"results\": []}");
+    assert_eq!(cache.get_stale("search", "word").await, Some(bytes));
+
+    server.serve_file("/search", b"{\"results\": [1]}");
+    let bytes = cache
+        .fetch_bytes_with("search", "word", &url, is_json)
+        .await
+        .unwrap();
+    assert_eq!(bytes, b"{\"results\": [1]}");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn evicts_the_oldest_entries_past_the_max_size() {
+    let dir = std::env::temp_dir().join(format!("anki_helper_cache_evict_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = Cache::new(&CacheConfig {
+        dir: dir.to_string_lossy().to_string(),
+        max_size_mb: 1,
+        ..Default::default()
+    });
+    let entry = vec![0; 300 * 1024];
+
+    for key in ["a", "b", "c", "d", "e"] {
+        cache.put("audio", key, &entry).await.unwrap();
+    }
+
+    let mut kept = Vec::new();
+    for key in ["a", "b", "c", "d", "e"] {
+        if cache.get("audio", key).await.is_some() {
+            kept.push(key);
+        }
+    }
+    assert_eq!(kept, vec!["c", "d", "e"]);
+
+    // small writes don't walk the cache until they add up
+    std::fs::write(dir.join("audio/untracked"), &entry).unwrap();
+    cache.put("audio", "f", &[0; 1024]).await.unwrap();
+    assert!(cache.get("audio", "c").await.is_some());
+    cache.put("audio", "g", &[0; 64 * 1024]).await.unwrap();
+    assert!(cache.get("audio", "c").await.is_none());
+    let _ = std::fs::remove_dir_all(&dir);
+}
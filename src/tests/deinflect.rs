@@ -0,0 +1,143 @@
+use crate::app::AppState;
+use crate::deinflect::*;
+use crate::jmdict::{DictEntry, Dictionary};
+use std::sync::Arc;
+
+#[test]
+fn deinflects_to_the_dictionary_form() {
+    let cases = [
+        ("食べられなかった", "食べる"),
+        ("食べない", "食べる"),
+        ("食べて", "食べる"),
+        ("食べた", "食べる"),
+        ("食べている", "食べる"),
+        ("食べてる", "食べる"),
+        ("した", "する"),
+        ("して", "する"),
+        ("しなかった", "する"),
+        ("勉強した", "勉強する"),
+        ("来た", "来る"),
+        ("来て", "来る"),
+        ("来なかった", "来る"),
+        ("きた", "くる"),
+        ("こない", "くる"),
+        ("買って", "買う"),
+        ("書いた", "書く"),
+        ("泳いで", "泳ぐ"),
+        ("話して", "話す"),
+        ("待った", "待つ"),
+        ("死んで", "死ぬ"),
+        ("遊んだ", "遊ぶ"),
+        ("読んでしまった", "読む"),
+        ("帰って", "帰る"),
+        ("行って", "行く"),
+        ("行っていた", "行く"),
+        ("高かった", "高い"),
+        ("高くなかった", "高い"),
+    ];
+    for (inflected, word) in cases {
+        assert!(
+            is_inflection_of(inflected, word),
+            "{} should deinflect to {}",
+            inflected,
+            word
+        );
+    }
+    assert!(!is_inflection_of("飲んだ", "食べる"));
+}
+
+#[test]
+fn keeps_the_reasons_in_order() {
+    let found = deinflect("食べられなかった")
+        .into_iter()
+        .find(|d| d.term == "食べる")
+        .unwrap();
+    assert_eq!(
+        found.reasons,
+        vec!["potential or passive", "negative", "past"]
+    );
+}
+
+#[test]
+fn inflections_deinflect_back_to_the_word() {
+    for word in ["食べる", "する", "来る", "行く", "高い"] {
+        let forms = inflections(word);
+        assert!(forms.contains(&word.to_string()));
+        for form in forms {
+            assert!(is_inflection_of(&form, word), "{} from {}", form, word);
+        }
+    }
+    assert!(inflections("食べる").contains(&"食べられなかった".to_string()));
+}
+
+#[test]
+fn finds_the_inflected_span() {
+    let cases = [
+        ("昨日パンを食べなかった。", "食べる", Some("食べなかった")),
+        ("パンを食べる。", "食べる", Some("食べる")),
+        ("もう食べてる？", "食べる", Some("食べてる")),
+        ("宿題をしてから寝た。", "する", Some("して")),
+        ("友達が来なかった。", "来る", Some("来なかった")),
+        ("学校に行っていた。", "行く", Some("行っていた")),
+        ("本を読んでしまった。", "読む", Some("読んでしまった")),
+        ("猫がいない。", "いる", Some("いない")),
+        ("高くなかった。", "高い", Some("高くなかった")),
+        ("水を飲む。", "食べる", None),
+        ("水を飲む。", "", None),
+    ];
+    for (sentence, word, expected) in cases {
+        let found = find_inflected(sentence, word).map(|(start, end)| &sentence[start..end]);
+        assert_eq!(found, expected, "{} in {}", word, sentence);
+    }
+}
+
+fn app_with_dictionary(words: &[&str]) -> AppState {
+    let entries = words
+        .iter()
+        .map(|word| DictEntry {
+            kanji: vec![word.to_string()],
+            ..Default::default()
+        })
+        .collect();
+    let mut app = AppState::default();
+    app.config.prefetch.window = 0;
+    app.dictionary = Some(Arc::new(Dictionary::new(entries)));
+    app
+}
+
+#[test]
+fn finds_the_dictionary_form_in_the_loaded_dictionaries() {
+    let app = app_with_dictionary(&["食べる", "行く"]);
+
+    let form = app.dictionary_form("食べられなかった").unwrap();
+    assert_eq!(form.term, "食べる");
+    assert_eq!(
+        form.reasons,
+        vec!["potential or passive", "negative", "past"]
+    );
+    assert_eq!(app.dictionary_form("行った").unwrap().term, "行く");
+
+    // already a dictionary word, or no form of it is in the dictionary
+    assert!(app.dictionary_form("食べる").is_none());
+    assert!(app.dictionary_form("飲んだ").is_none());
+    assert!(AppState::default().dictionary_form("食べた").is_none());
+}
+
+#[tokio::test]
+async fn search_input_is_normalized_to_the_dictionary_form() {
+    let mut app = app_with_dictionary(&["食べる"]);
+    app.input.text = " 食べなかった ".to_string();
+
+    app.confirm_search_query().await;
+
+    let words: Vec<&str> = app
+        .expressions
+        .iter()
+        .map(|e| e.dict_word.as_str())
+        .collect();
+    assert_eq!(words, vec!["食べる"]);
+    assert_eq!(
+        app.info.msg.as_deref(),
+        Some("食べなかった → 食べる (negative, past)")
+    );
+}
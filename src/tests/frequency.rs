@@ -0,0 +1,127 @@
+use crate::app::{AppState, Expression};
+use crate::frequency::{FrequencyConfig, ThresholdMode};
+use crate::yomitan::Frequency;
+
+fn ranked(word: &str, rank: Option<u64>) -> Expression {
+    let mut exp = Expression::from(word.to_string(), None, None, None);
+    exp.frequencies = rank
+        .map(|value| Frequency {
+            dict: "JPDB".to_string(),
+            expression: word.to_string(),
+            reading: None,
+            value,
+            display: value.to_string(),
+        })
+        .into_iter()
+        .collect();
+    exp
+}
+
+fn app(frequency: FrequencyConfig, words: &[(&str, Option<u64>)]) -> AppState {
+    let mut app = AppState::default();
+    app.config.frequency = frequency;
+    app.expressions = words
+        .iter()
+        .map(|&(word, rank)| ranked(word, rank))
+        .collect();
+    app
+}
+
+fn words(app: &AppState, indexes: &[usize]) -> Vec<String> {
+    indexes
+        .iter()
+        .map(|&i| app.expressions[i].dict_word.clone())
+        .collect()
+}
+
+fn hide_above(threshold: u64) -> FrequencyConfig {
+    FrequencyConfig {
+        sort: true,
+        threshold,
+        threshold_mode: ThresholdMode::Hide,
+    }
+}
+
+#[test]
+fn sorts_by_rank_keeping_unranked_words_in_order_at_the_end() {
+    let mut app = app(
+        FrequencyConfig::default(),
+        &[
+            ("稀", None),
+            ("猫", Some(900)),
+            ("犬", Some(300)),
+            ("鳥", None),
+            ("見る", Some(20)),
+        ],
+    );
+    app.selected_expression = Some(2);
+
+    app.apply_frequency_options(true);
+
+    let all: Vec<usize> = (0..app.expressions.len()).collect();
+    assert_eq!(words(&app, &all), vec!["見る", "犬", "猫", "稀", "鳥"]);
+    // the selected expression stays selected
+    assert_eq!(app.selected_expression, Some(1));
+}
+
+#[test]
+fn hides_words_above_the_threshold_without_removing_them() {
+    let mut app = app(
+        hide_above(500),
+        &[("猫", Some(900)), ("犬", Some(300)), ("稀", None)],
+    );
+    app.config.prefetch.window = 0;
+    app.selected_expression = Some(0);
+
+    app.apply_frequency_options(false);
+
+    assert_eq!(app.expressions.len(), 3);
+    assert_eq!(words(&app, &app.visible_expressions()), vec!["犬", "稀"]);
+    // the selection moves off the hidden expression
+    assert_eq!(app.selected_expression, Some(1));
+    app.select_next_exp();
+    assert_eq!(app.selected_expression, Some(2));
+    app.select_next_exp();
+    assert_eq!(app.selected_expression, Some(1));
+
+    // raising the threshold brings it back
+    app.config.frequency.threshold = 1000;
+    assert_eq!(
+        words(&app, &app.visible_expressions()),
+        vec!["猫", "犬", "稀"]
+    );
+
+    // flagged expressions stay in the list
+    app.config.frequency = FrequencyConfig {
+        threshold_mode: ThresholdMode::Flag,
+        ..hide_above(500)
+    };
+    assert_eq!(app.visible_expressions(), vec![0, 1, 2]);
+}
+
+#[test]
+fn pushes_expressions_in_rank_order_when_sorting() {
+    let mut app = app(
+        hide_above(500),
+        &[("見る", Some(20)), ("猫", Some(900)), ("稀", None)],
+    );
+    app.selected_expression = Some(1);
+    app.expressions_state.select(Some(1));
+
+    let i = app.insert_position(&ranked("犬", Some(300)));
+    assert_eq!(i, 1);
+    assert_eq!(app.push_expression(ranked("犬", Some(300))), 1);
+    assert_eq!(app.push_expression(ranked("鳥", None)), 4);
+
+    let all: Vec<usize> = (0..app.expressions.len()).collect();
+    assert_eq!(words(&app, &all), vec!["見る", "犬", "猫", "稀", "鳥"]);
+    assert_eq!(
+        words(&app, &app.visible_expressions()),
+        vec!["見る", "犬", "稀", "鳥"]
+    );
+    // the selection follows the expression it was on
+    assert_eq!(app.selected_expression, Some(2));
+
+    app.config.frequency.sort = false;
+    assert_eq!(app.push_expression(ranked("水", Some(1))), 5);
+}
@@ -0,0 +1,79 @@
+use crate::furigana::*;
+
+fn seg(text: &str, reading: Option<&str>) -> Segment {
+    Segment {
+        text: text.to_string(),
+        reading: reading.map(String::from),
+    }
+}
+
+#[test]
+fn aligns_readings_with_the_kanji_runs() {
+    assert_eq!(
+        align("食べる", "たべる"),
+        vec![seg("食", Some("た")), seg("べる", None)]
+    );
+    assert_eq!(
+        align("お見舞い", "オミマイ"),
+        vec![seg("お", None), seg("見舞", Some("みま")), seg("い", None)]
+    );
+    assert_eq!(align("日本", "にほん"), vec![seg("日本", Some("にほん"))]);
+    // kana only, or a reading that doesn't fit, isn't split
+    assert_eq!(align("たべる", "たべる"), vec![seg("たべる", None)]);
+    assert_eq!(align("食べる", "のむ"), vec![seg("食べる", Some("のむ"))]);
+}
+
+#[test]
+fn round_trips_through_anki_markup() {
+    let segments = vec![
+        seg("パンを", None),
+        seg("食", Some("た")),
+        seg("べた", None),
+    ];
+
+    let anki = to_anki(&segments);
+
+    assert_eq!(anki, "パンを 食[た]べた");
+    assert_eq!(parse_anki(&anki), segments);
+    assert_eq!(to_anki(&align("食べる", "たべる")), "食[た]べる");
+}
+
+#[test]
+fn parses_okurigana_written_inside_the_base() {
+    assert_eq!(
+        parse_anki("毎朝[まいあさ] 食べる[たべる]。"),
+        vec![
+            seg("毎朝", Some("まいあさ")),
+            seg("食", Some("た")),
+            seg("べる", None),
+            seg("。", None),
+        ]
+    );
+}
+
+#[test]
+fn lays_readings_over_their_text_and_breaks_lines() {
+    let segments = parse_anki("日本[にほん]へ 行[い]く");
+
+    let lines = ruby_lines(&segments, 20);
+    assert_eq!(
+        lines,
+        vec![(
+            "にほん\u{a0}\u{a0}い\u{a0}\u{a0}".to_string(),
+            "日本\u{a0}\u{a0}へ行く".to_string()
+        )]
+    );
+
+    // 日本 takes 6 columns for its reading, the rest goes on the next line
+    let lines = ruby_lines(&segments, 7);
+    assert_eq!(
+        lines,
+        vec![
+            ("にほん".to_string(), "日本\u{a0}\u{a0}".to_string()),
+            (
+                "\u{a0}\u{a0}い\u{a0}\u{a0}".to_string(),
+                "へ行く".to_string()
+            ),
+        ]
+    );
+}
@@ -0,0 +1,158 @@
+use crate::jmdict::*;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMdict [
+<!ENTITY v1 "Ichidan verb">
+]>
+<JMdict>
+<entry>
+<ent_seq>1358280</ent_seq>
+<k_ele>
+<keb>食べる</keb>
+</k_ele>
+<k_ele>
+<keb>喰べる</keb>
+</k_ele>
+<r_ele>
+<reb>たべる</reb>
+</r_ele>
+<sense>
+<pos>&v1;</pos>
+<pos>&vt;</pos>
+<gloss>to eat</gloss>
+<gloss xml:lang="ger">essen</gloss>
+</sense>
+<sense>
+<gloss>to live on (e.g. a salary)</gloss>
+</sense>
+</entry>
+<entry>
+<ent_seq>2000000</ent_seq>
+<r_ele>
+<reb>あれ</reb>
+</r_ele>
+<sense>
+<gloss>&lt;that&gt; &amp; &quot;it&quot;</gloss>
+</sense>
+</entry>
+</JMdict>
+"#;
+
+const JSON: &str = r#"{"version": "3.5.0", "words": [{
+    "id": "1358280",
+    "kanji": [{"common": true, "text": "食べる", "tags": []}],
+    "kana": [{"common": true, "text": "たべる", "tags": [], "appliesToKanji": ["*"]}],
+    "sense": [{
+        "partOfSpeech": ["v1", "vt"],
+        "gloss": [{"lang": "eng", "text": "to eat"}, {"lang": "ger", "text": "essen"}]
+    }]
+}]}"#;
+
+fn sense(pos: &[&str], glosses: &[&str]) -> DictSense {
+    DictSense {
+        pos: pos.iter().map(|p| p.to_string()).collect(),
+        glosses: glosses.iter().map(|g| g.to_string()).collect(),
+    }
+}
+
+fn taberu() -> DictEntry {
+    DictEntry {
+        kanji: vec!["食べる".to_string(), "喰べる".to_string()],
+        readings: vec!["たべる".to_string()],
+        senses: vec![
+            sense(&["v1", "vt"], &["to eat"]),
+            sense(&["v1", "vt"], &["to live on (e.g. a salary)"]),
+        ],
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "anki_helper_jmdict_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn decodes_xml_entities() {
+    assert_eq!(
+        decode_entities("&lt;b&gt; &quot;a&apos;s&quot; &amp;amp;"),
+        "<b> \"a's\" &amp;"
+    );
+}
+
+#[test]
+fn parses_the_xml_inheriting_pos_within_an_entry() {
+    let dir = temp_dir("xml");
+    let path = dir.join("JMdict_e");
+    std::fs::write(&path, XML).unwrap();
+
+    let entries = parse_xml(&path).unwrap();
+
+    let are = DictEntry {
+        kanji: Vec::new(),
+        readings: vec!["あれ".to_string()],
+        senses: vec![sense(&[], &["<that> & \"it\""])],
+    };
+    assert_eq!(entries, vec![taberu(), are]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn parses_the_simplified_json_keeping_english_glosses() {
+    let dir = temp_dir("json");
+    let path = dir.join("jmdict-eng.json");
+    std::fs::write(&path, JSON).unwrap();
+
+    let entries = parse_simplified_json(&path).unwrap();
+
+    let mut expected = taberu();
+    expected.kanji.truncate(1);
+    expected.senses.truncate(1);
+    assert_eq!(entries, vec![expected]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rebuilds_the_index_when_the_dictionary_is_newer() {
+    let dir = temp_dir("index");
+    let config = JmdictConfig {
+        enabled: true,
+        path: dir.join("JMdict_e").to_string_lossy().to_string(),
+        index_path: dir.join("index.json").to_string_lossy().to_string(),
+    };
+    assert!(load_or_build_index(&config).unwrap().is_none());
+
+    std::fs::write(&config.path, XML).unwrap();
+    let dict = load_or_build_index(&config).unwrap().unwrap();
+    assert_eq!(dict.len(), 2);
+
+    let only_are = XML
+        .split_once("<entry>\n<ent_seq>1358280")
+        .unwrap()
+        .0
+        .to_string()
+        + XML.split_once("</entry>\n").unwrap().1;
+    std::fs::write(&config.path, only_are).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&config.index_path)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(60))
+        .unwrap();
+
+    let dict = load_or_build_index(&config).unwrap().unwrap();
+    assert_eq!(dict.len(), 1);
+    assert!(dict.lookup("食べる").is_empty());
+
+    // the index is used as is once the dictionary is gone
+    std::fs::remove_file(&config.path).unwrap();
+    assert_eq!(load_or_build_index(&config).unwrap().unwrap().len(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,130 @@
+use crate::journal::*;
+use std::collections::HashMap;
+
+fn batch(dict_word: &str, id: u64) -> JournalBatch {
+    let note = NoteSnapshot {
+        id: id as u128,
+        dict_word: dict_word.to_string(),
+        fields: HashMap::from([("Sentence".to_string(), String::new())]),
+    };
+    JournalBatch {
+        id,
+        notes: vec![note],
+        created: Vec::new(),
+        undone: false,
+    }
+}
+
+#[test]
+fn keeps_the_newest_batches() {
+    let path = std::env::temp_dir().join(format!("anki_helper_trim_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let config = JournalConfig {
+        enabled: true,
+        path: path.to_string_lossy().to_string(),
+        max_batches: 2,
+    };
+
+    assert!(read_journal(&config).unwrap().is_empty());
+    for (i, word) in ["食べる", "飲む", "見る"].iter().enumerate() {
+        record_batch(&config, batch(word, i as u64 + 1)).unwrap();
+    }
+    mark_undone(&config, 3).unwrap();
+
+    let batches = read_journal(&config).unwrap();
+    let kept: Vec<(&str, bool)> = batches
+        .iter()
+        .map(|batch| (batch.notes[0].dict_word.as_str(), batch.undone))
+        .collect();
+    assert_eq!(kept, vec![("飲む", false), ("見る", true)]);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn finds_notes_a_later_update_rewrote() {
+    let mut first = batch("食べる", 1);
+    first.notes.push(batch("飲む", 2).notes.remove(0));
+    let mut undone = batch("食べる", 3);
+    undone.notes[0].id = 1;
+    undone.undone = true;
+    let mut later = batch("飲む", 4);
+    later.notes[0].id = 2;
+
+    let batches = vec![first.clone(), undone.clone(), later.clone()];
+    assert_eq!(rewritten_notes(&batches, &first), vec![2]);
+    assert!(rewritten_notes(&batches, &later).is_empty());
+
+    let batches = vec![first.clone(), undone];
+    assert!(rewritten_notes(&batches, &first).is_empty());
+}
+
+#[test]
+fn backs_up_an_unreadable_journal() {
+    let dir = std::env::temp_dir().join(format!("anki_helper_corrupt_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("journal.json");
+    std::fs::write(&path, "[{\"id\": 1, \"notes\": [").unwrap();
+    let config = JournalConfig {
+        enabled: true,
+        path: path.to_string_lossy().to_string(),
+        max_batches: 2,
+    };
+
+    assert!(read_journal(&config).is_err());
+    let warning = record_batch(&config, batch("食べる", 1)).unwrap().unwrap();
+
+    assert!(warning.starts_with(&format!("Undo journal `{}` was unreadable", config.path)));
+
+    let batches = read_journal(&config).unwrap();
+    assert_eq!(batches.len(), 1);
+    let files: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(files.len(), 2, "{:?}", files);
+    assert!(files.iter().any(|file| file.ends_with(".bak")));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn drops_a_batch_that_wrote_nothing() {
+    let path = std::env::temp_dir().join(format!("anki_helper_empty_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let config = JournalConfig {
+        enabled: true,
+        path: path.to_string_lossy().to_string(),
+        max_batches: 5,
+    };
+
+    record_batch(&config, batch("食べる", 1)).unwrap();
+    record_batch(
+        &config,
+        JournalBatch {
+            notes: Vec::new(),
+            ..batch("飲む", 2)
+        },
+    )
+    .unwrap();
+    record_batch(
+        &config,
+        JournalBatch {
+            notes: Vec::new(),
+            ..batch("見る", 3)
+        },
+    )
+    .unwrap();
+    finish_batch(&config, 2, Vec::new()).unwrap();
+    finish_batch(&config, 3, vec![7]).unwrap();
+
+    let batches = read_journal(&config).unwrap();
+    let kept: Vec<(u64, Vec<u128>)> = batches.iter().map(|b| (b.id, b.created.clone())).collect();
+    assert_eq!(kept, vec![(1, Vec::new()), (3, vec![7])]);
+    // a later update to a note the first one added
+    assert_eq!(rewritten_notes(&batches, &batches[0]), Vec::<u128>::new());
+    let mut later = batch("見る", 4);
+    later.notes[0].id = 7;
+    let batches = vec![batches[1].clone(), later];
+    assert_eq!(rewritten_notes(&batches, &batches[0]), vec![7]);
+    let _ = std::fs::remove_file(&path);
+}
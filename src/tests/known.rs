@@ -0,0 +1,78 @@
+use crate::app::{Expression, Sentence};
+use crate::known::KnownWords;
+
+fn known(words: &[&str]) -> KnownWords {
+    KnownWords::from_words(words.iter().map(|w| w.to_string()))
+}
+
+fn sentence(text: &str) -> Sentence {
+    let exp = Expression::from("食べる".to_string(), None, None, None);
+    Sentence::from(text, None, None, None, "", "", &exp)
+}
+
+#[test]
+fn segments_by_known_words_and_their_inflections() {
+    let known = known(&["パン", "食べる", "毎朝"]);
+
+    assert_eq!(
+        known.segment("毎朝パンを食べたい。"),
+        vec!["毎朝", "パン", "を", "食べたい", "。"]
+    );
+    // unknown runs are split where the script changes
+    assert_eq!(
+        known.segment("新聞を読んだ"),
+        vec!["新聞", "を", "読", "んだ"]
+    );
+}
+
+#[test]
+fn counts_inflections_as_known_but_not_words_sharing_a_stem() {
+    let known = known(&["食べる", "見る", "パン"]);
+
+    assert_eq!(known.count_unknown(&sentence("パンを食べた。")), 0);
+    assert_eq!(known.count_unknown(&sentence("見ている。")), 0);
+    assert_eq!(known.count_unknown(&sentence("パンを食う。")), 1);
+    assert_eq!(known.count_unknown(&sentence("写真を見せる。")), 2);
+
+    // Immersion Kit's word list is used as is
+    let mut listed = sentence("パンを食べた。");
+    listed.words = ["パン", "を", "食べた", "。", "ケーキ"]
+        .map(String::from)
+        .to_vec();
+    assert_eq!(known.count_unknown(&listed), 1);
+}
+
+#[test]
+fn ranks_sentences_closest_to_one_unknown_word_first() {
+    let known = known(&["パン", "食べる", "毎朝"]);
+    let mut exp = Expression::from("食べる".to_string(), None, None, None);
+    exp.unfiltered_sentences = [
+        "パンを食べた。",
+        "昨日学校で友達とパンを食べた。",
+        "毎朝ケーキを食べる。",
+    ]
+    .map(sentence)
+    .to_vec();
+
+    exp.rank_sentences(&known);
+
+    let ranked: Vec<(&str, Option<usize>)> = exp
+        .unfiltered_sentences
+        .iter()
+        .map(|s| (s.sentence.as_str(), s.unknown_words))
+        .collect();
+    assert_eq!(
+        ranked,
+        vec![
+            ("毎朝ケーキを食べる。", Some(1)),
+            ("パンを食べた。", Some(0)),
+            ("昨日学校で友達とパンを食べた。", Some(2)),
+        ]
+    );
+
+    // nothing is ranked without known words
+    let mut unranked = Expression::from("食べる".to_string(), None, None, None);
+    unranked.unfiltered_sentences = vec![sentence("パンを食べた。")];
+    unranked.rank_sentences(&KnownWords::default());
+    assert_eq!(unranked.unfiltered_sentences[0].unknown_words, None);
+}
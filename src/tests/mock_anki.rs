@@ -0,0 +1,459 @@
+//! An in-process AnkiConnect stand-in, recording every request it gets.
+
+use base64::Engine;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The mock's note type has this as its first field, which `addNote` checks for duplicates.
+pub const EXPRESSION: &str = "Expression";
+/// Fields of the mock's note type.
+pub const FIELDS: [&str; 6] = [
+    EXPRESSION,
+    "Sentence",
+    "Audio",
+    "Picture",
+    "Reading",
+    "Definition",
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct MockNote {
+    pub id: u128,
+    pub deck: String,
+    pub model: String,
+    pub tags: Vec<String>,
+    /// In the note type's order.
+    pub fields: Vec<(String, String)>,
+}
+
+impl MockNote {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn set_field(&mut self, name: &str, value: String) {
+        match self.fields.iter_mut().find(|(field, _)| field == name) {
+            Some((_, current)) => *current = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    notes: Vec<MockNote>,
+    /// Every request body, in the order they came in, with the actions of a `multi` after it.
+    requests: Vec<Value>,
+    /// HTTP requests for AnkiConnect, a `multi` counts once.
+    round_trips: usize,
+    /// Stored media, by file name.
+    media: Vec<(String, Vec<u8>)>,
+    /// Served for `GET` requests, by path.
+    files: HashMap<String, Vec<u8>>,
+    /// Actions that answer with an error instead of a result.
+    failures: HashMap<String, String>,
+    next_id: u128,
+}
+
+pub struct MockAnki {
+    state: Arc<Mutex<MockState>>,
+    url: String,
+}
+
+impl MockAnki {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(MockState {
+            next_id: 1_700_000_000_000,
+            ..Default::default()
+        }));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, server_state.clone()));
+            }
+        });
+
+        Self { state, url }
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Adds a note of the mock's note type, with `fields` set & the rest empty.
+    pub fn add_note(&self, fields: &[(&str, &str)]) -> u128 {
+        let fields: Vec<(&str, &str)> = FIELDS
+            .iter()
+            .map(|name| {
+                let value = fields.iter().find(|(field, _)| field == name);
+                (*name, value.map_or("", |(_, value)| value))
+            })
+            .collect();
+        self.add_other_note(&fields)
+    }
+
+    /// Adds a note of another note type, that only has `fields`.
+    pub fn add_other_note(&self, fields: &[(&str, &str)]) -> u128 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.notes.push(MockNote {
+            id,
+            deck: "Default".to_string(),
+            model: "Basic".to_string(),
+            tags: Vec::new(),
+            fields: fields
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        });
+        id
+    }
+
+    /// Sets a field like it was edited in Anki.
+    pub fn edit_note(&self, id: u128, field: &str, value: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(note) = state.notes.iter_mut().find(|note| note.id == id) {
+            note.set_field(field, value.to_string());
+        }
+    }
+
+    /// Every later `action` request gets `{"result": null, "error": error}`.
+    pub fn fail(&self, action: &str, error: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.failures.insert(action.to_string(), error.to_string());
+    }
+
+    pub fn note(&self, id: u128) -> Option<MockNote> {
+        let state = self.state.lock().unwrap();
+        state.notes.iter().find(|note| note.id == id).cloned()
+    }
+
+    /// The params of every `action` request.
+    pub fn requests(&self, action: &str) -> Vec<Value> {
+        let state = self.state.lock().unwrap();
+        state
+            .requests
+            .iter()
+            .filter(|req| req["action"] == action)
+            .map(|req| req["params"].clone())
+            .collect()
+    }
+
+    pub fn round_trips(&self) -> usize {
+        self.state.lock().unwrap().round_trips
+    }
+
+    /// Stored media file names, in the order they came in.
+    pub fn media(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.media.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    pub fn media_file(&self, name: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state
+            .media
+            .iter()
+            .find(|(stored, _)| stored == name)
+            .map(|(_, bytes)| bytes.clone())
+    }
+
+    /// Serves `bytes` at `path`, returning its url.
+    pub fn serve_file(&self, path: &str, bytes: &[u8]) -> String {
+        let mut state = self.state.lock().unwrap();
+        state.files.insert(path.to_string(), bytes.to_vec());
+        format!("{}{}", self.url, path)
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let Some((head, body)) = read_request(&mut stream).await else {
+        return;
+    };
+
+    let (status, content_type, body) = match head.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", path, ..] => match state.lock().unwrap().files.get(path) {
+            Some(bytes) => ("200 OK", "application/octet-stream", bytes.clone()),
+            None => ("404 Not Found", "text/plain", b"not found".to_vec()),
+        },
+        _ => {
+            let mut state = state.lock().unwrap();
+            state.round_trips += 1;
+            let response = match serde_json::from_slice::<Value>(&body) {
+                Ok(req) => respond(&mut state, req),
+                Err(err) => json!({ "result": null, "error": err.to_string() }),
+            };
+            (
+                "200 OK",
+                "application/json",
+                response.to_string().into_bytes(),
+            )
+        }
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&body).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Reads the headers up to the blank line, then `Content-Length` bytes of body.
+async fn read_request(stream: &mut TcpStream) -> Option<(String, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i + 4;
+        }
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let content_length: usize = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0);
+
+    let mut body = buf[header_end..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    Some((head, body))
+}
+
+fn respond(state: &mut MockState, req: Value) -> Value {
+    state.requests.push(req.clone());
+    let action = req["action"].as_str().unwrap_or_default();
+    let params = &req["params"];
+
+    if let Some(error) = state.failures.get(action) {
+        return json!({ "result": null, "error": error });
+    }
+
+    let result = match action {
+        // with `version` 6, each action's reply has its own `result` & `error`
+        "multi" => Ok(json!(params["actions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|action| respond(state, action.clone()))
+            .collect::<Vec<_>>())),
+        "findNotes" => Ok(find_notes(
+            state,
+            params["query"].as_str().unwrap_or_default(),
+        )),
+        "notesInfo" => Ok(notes_info(state, &params["notes"])),
+        "updateNoteFields" => update_note_fields(state, &params["note"]),
+        "storeMediaFile" => store_media_file(state, params),
+        "addNote" => add_note(state, &params["note"]),
+        "deleteNotes" => {
+            let ids: Vec<u128> = params["notes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|id| id.as_u64().map(u128::from))
+                .collect();
+            state.notes.retain(|note| !ids.contains(&note.id));
+            Ok(Value::Null)
+        }
+        "guiEditNote" => match params["note"].as_u64() {
+            Some(id) if state.notes.iter().any(|note| note.id == id as u128) => Ok(Value::Null),
+            _ => Err(format!("note was not found: {}", params["note"])),
+        },
+        _ => Err("unsupported action".to_string()),
+    };
+
+    match result {
+        Ok(result) => json!({ "result": result, "error": null }),
+        Err(error) => json!({ "result": null, "error": error }),
+    }
+}
+
+/// `("field:*term*" OR "field:term")` searches match on that field. Otherwise search terms like
+/// `is:new` are ignored, the rest have to be in one of the note's fields.
+fn find_notes(state: &MockState, query: &str) -> Value {
+    if let Some(clauses) = query.strip_prefix('(').and_then(|q| q.strip_suffix(')')) {
+        let clauses: Vec<(String, String)> = clauses
+            .split(" OR ")
+            .filter_map(|clause| {
+                let clause = clause.strip_prefix('"')?.strip_suffix('"')?;
+                let (field, term) = clause.split_once(':')?;
+                // the mock's fields & terms never need escaped wildcards
+                Some((field.replace('\\', ""), term.replace('\\', "")))
+            })
+            .collect();
+        let ids: Vec<u128> = state
+            .notes
+            .iter()
+            .filter(|note| {
+                clauses.iter().any(|(field, term)| {
+                    let Some((_, value)) = note.fields.iter().find(|(name, _)| name == field)
+                    else {
+                        return false;
+                    };
+                    match term.strip_prefix('*').and_then(|t| t.strip_suffix('*')) {
+                        Some(term) => value.contains(term),
+                        None => value == term,
+                    }
+                })
+            })
+            .map(|note| note.id)
+            .collect();
+        return json!(ids);
+    }
+
+    let terms: Vec<&str> = query
+        .split_whitespace()
+        .filter(|term| !term.contains(':'))
+        .collect();
+    let ids: Vec<u128> = state
+        .notes
+        .iter()
+        .filter(|note| {
+            terms
+                .iter()
+                .all(|term| note.fields.iter().any(|(_, value)| value.contains(term)))
+        })
+        .map(|note| note.id)
+        .collect();
+    json!(ids)
+}
+
+fn notes_info(state: &MockState, ids: &Value) -> Value {
+    let infos: Vec<Value> = ids
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|id| {
+            let Some(note) = state
+                .notes
+                .iter()
+                .find(|note| Some(note.id) == id.as_u64().map(u128::from))
+            else {
+                return json!({});
+            };
+            let fields: Map<String, Value> = note
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(order, (name, value))| {
+                    (name.clone(), json!({ "value": value, "order": order }))
+                })
+                .collect();
+            json!({
+                "noteId": note.id,
+                "modelName": note.model,
+                "tags": note.tags,
+                "fields": fields,
+            })
+        })
+        .collect();
+    json!(infos)
+}
+
+fn update_note_fields(state: &mut MockState, params: &Value) -> Result<Value, String> {
+    let id = params["id"].as_u64().map(u128::from);
+    let Some(note) = state.notes.iter_mut().find(|note| Some(note.id) == id) else {
+        return Err(format!("note was not found: {}", params["id"]));
+    };
+
+    for (name, value) in params["fields"].as_object().into_iter().flatten() {
+        note.set_field(name, value.as_str().unwrap_or_default().to_string());
+    }
+    Ok(Value::Null)
+}
+
+fn add_note(state: &mut MockState, params: &Value) -> Result<Value, String> {
+    let fields: Vec<(String, String)> = params["fields"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| (name.clone(), value.as_str().unwrap_or_default().to_string()))
+        .collect();
+    let expression = fields
+        .iter()
+        .find(|(name, _)| name == EXPRESSION)
+        .map(|(_, value)| value.as_str())
+        .unwrap_or_default();
+    if expression.is_empty() {
+        return Err("cannot create note because it is empty".to_string());
+    }
+    let allow_duplicate = params["options"]["allowDuplicate"]
+        .as_bool()
+        .unwrap_or(false);
+    if !allow_duplicate
+        && state
+            .notes
+            .iter()
+            .any(|note| note.field(EXPRESSION) == Some(expression))
+    {
+        return Err("cannot create note because it is a duplicate".to_string());
+    }
+
+    let id = state.next_id;
+    state.next_id += 1;
+    state.notes.push(MockNote {
+        id,
+        deck: params["deckName"].as_str().unwrap_or_default().to_string(),
+        model: params["modelName"].as_str().unwrap_or_default().to_string(),
+        tags: params["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|tag| tag.as_str().map(|tag| tag.to_string()))
+            .collect(),
+        fields,
+    });
+    Ok(json!(id))
+}
+
+/// Like Anki, a different file with the same name is stored under a new name, unless
+/// `deleteExisting` is set.
+fn store_media_file(state: &mut MockState, params: &Value) -> Result<Value, String> {
+    let filename = params["filename"].as_str().unwrap_or_default();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(params["data"].as_str().unwrap_or_default())
+        .map_err(|err| err.to_string())?;
+    let delete_existing = params["deleteExisting"].as_bool().unwrap_or(true);
+
+    let existing = state.media.iter().position(|(name, _)| name == filename);
+    let name = match existing {
+        Some(i) if state.media[i].1 == bytes => return Ok(json!(filename)),
+        Some(i) if delete_existing => {
+            state.media.remove(i);
+            filename.to_string()
+        }
+        Some(_) => match filename.rsplit_once('.') {
+            Some((stem, ext)) => format!("{}-{}.{}", stem, state.media.len(), ext),
+            None => format!("{}-{}", filename, state.media.len()),
+        },
+        None => filename.to_string(),
+    };
+    state.media.push((name.clone(), bytes));
+    Ok(json!(name))
+}
@@ -0,0 +1,15 @@
+mod anki;
+mod cache;
+mod deinflect;
+mod frequency;
+mod furigana;
+mod jmdict;
+mod journal;
+mod known;
+mod mock_anki;
+mod novels;
+mod sources;
+mod subtitles;
+mod tasks;
+mod tatoeba;
+mod yomitan;
@@ -0,0 +1,51 @@
+use crate::app::Expression;
+use crate::sources::novels::*;
+use crate::sources::SentenceSource;
+
+#[tokio::test]
+async fn skips_books_it_cant_read() {
+    let dir = std::env::temp_dir().join(format!("anki_helper_novels_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("a_broken.txt")).unwrap();
+    std::fs::write(dir.join("b_broken.epub"), b"not a zip").unwrap();
+    std::fs::write(dir.join("c_novel.txt"), "朝ご飯を食べた。それから寝た。").unwrap();
+
+    let (index, skipped) = build_index(&dir).unwrap();
+
+    let books: Vec<&str> = index.books.iter().map(|b| b.title.as_str()).collect();
+    assert_eq!(books, vec!["c_novel"]);
+    let skipped: Vec<&str> = skipped
+        .iter()
+        .filter_map(|err| err.strip_prefix("Skipped novel `"))
+        .filter_map(|err| err.split_once('`').map(|(path, _)| path))
+        .collect();
+    let broken = ["a_broken.txt", "b_broken.epub"].map(|name| {
+        let path = dir.join(name);
+        path.canonicalize()
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    });
+    assert_eq!(
+        skipped,
+        broken.iter().map(String::as_str).collect::<Vec<_>>()
+    );
+
+    let source = NovelSource::new(&NovelsConfig {
+        dir: dir.to_string_lossy().to_string(),
+    });
+    // keep the errors out of the repo's err_log.txt
+    std::fs::remove_dir_all(dir.join("a_broken.txt")).unwrap();
+    std::fs::remove_file(dir.join("b_broken.epub")).unwrap();
+    let exp = Expression::from("食べる".to_string(), None, None, None);
+
+    let res = source.fetch(&exp).await.unwrap();
+
+    let found: Vec<(&str, &str)> = res
+        .sentences
+        .iter()
+        .map(|s| (s.sentence.as_str(), s.media_title.as_str()))
+        .collect();
+    assert_eq!(found, vec![("朝ご飯を食べた。", "c_novel")]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,162 @@
+use crate::anki::ConfigJson;
+use crate::app::{Expression, Sentence};
+use crate::sources::*;
+use futures_util::future::BoxFuture;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Returns `found` sentences, or an error if it's `None`, counting how often it was asked.
+struct FakeSource {
+    name: &'static str,
+    found: Option<usize>,
+    calls: AtomicUsize,
+}
+
+impl FakeSource {
+    fn new(name: &'static str, found: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            found,
+            calls: AtomicUsize::new(0),
+        })
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl SentenceSource for FakeSource {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn label(&self) -> &'static str {
+        self.name
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        expression: &'a Expression,
+    ) -> BoxFuture<'a, Result<SourceResult, SourceError>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move {
+            let found = self.found.ok_or("offline")?;
+            Ok(SourceResult {
+                sentences: (0..found)
+                    .map(|i| {
+                        let text = format!("{}の文{}。", self.name, i);
+                        Sentence::from(&text, None, None, None, self.name, "", expression)
+                    })
+                    .collect(),
+                readings: vec![format!("{}の読み", self.name)],
+                ..SourceResult::default()
+            })
+        })
+    }
+}
+
+fn registry_of(sources: &[&Arc<FakeSource>]) -> SourceRegistry {
+    SourceRegistry::new(
+        sources
+            .iter()
+            .map(|source| (*source).clone() as Arc<dyn SentenceSource>)
+            .collect(),
+    )
+}
+
+fn expression() -> Expression {
+    Expression::from("食べる".to_string(), None, None, None)
+}
+
+#[tokio::test]
+async fn falls_back_in_order_and_merges_readings() {
+    let (failing, empty, found, unused) = (
+        FakeSource::new("failing", None),
+        FakeSource::new("empty", Some(0)),
+        FakeSource::new("found", Some(2)),
+        FakeSource::new("unused", Some(1)),
+    );
+    let registry = registry_of(&[&failing, &empty, &found, &unused]);
+
+    let (res, label) = registry.fetch(&expression(), false).await.unwrap();
+
+    assert_eq!(label, "found");
+    assert_eq!(res.sentences.len(), 2);
+    assert_eq!(res.readings, vec!["emptyの読み", "foundの読み"]);
+    let calls: Vec<usize> = [&failing, &empty, &found, &unused]
+        .iter()
+        .map(|source| source.calls())
+        .collect();
+    assert_eq!(calls, vec![1, 1, 1, 0]);
+}
+
+#[tokio::test]
+async fn reports_every_error_when_nothing_is_found() {
+    let registry = registry_of(&[
+        &FakeSource::new("first", None),
+        &FakeSource::new("second", None),
+    ]);
+
+    let err = registry.fetch(&expression(), false).await.unwrap_err();
+    assert_eq!(err.to_string(), "first: offline | second: offline");
+
+    let registry = registry_of(&[&FakeSource::new("empty", Some(0))]);
+    let err = registry.fetch(&expression(), false).await.unwrap_err();
+    assert_eq!(err.to_string(), "0 Sentences Found!");
+}
+
+#[tokio::test]
+async fn skip_ik_skips_immersion_kit_wherever_it_is() {
+    let (subtitles, ik, massif) = (
+        FakeSource::new("subtitles", Some(0)),
+        FakeSource::new("immersion_kit", Some(1)),
+        FakeSource::new("massif", Some(1)),
+    );
+    let registry = registry_of(&[&subtitles, &ik, &massif]);
+
+    let (_, label) = registry.fetch(&expression(), true).await.unwrap();
+
+    assert_eq!(label, "massif");
+    assert_eq!((subtitles.calls(), ik.calls()), (1, 0));
+}
+
+#[tokio::test]
+async fn skip_ik_keeps_immersion_kit_when_its_the_only_source() {
+    let only = FakeSource::new("immersion_kit", Some(1));
+    let registry = registry_of(&[&only]);
+
+    let (_, label) = registry.fetch(&expression(), true).await.unwrap();
+
+    assert_eq!(label, "immersion_kit");
+    assert_eq!(only.calls(), 1);
+}
+
+#[test]
+fn builds_the_chain_from_the_config() {
+    let config = ConfigJson {
+        sources: ["massif", "nope", "immersion_kit", "massif"]
+            .map(String::from)
+            .to_vec(),
+        ..Default::default()
+    };
+
+    let (registry, errors) = SourceRegistry::from_config(&config);
+
+    assert_eq!(registry.names(), vec!["massif", "immersion_kit"]);
+    assert_eq!(
+        errors,
+        vec![
+            "Unknown Sentence Source: `nope`",
+            "Duplicate Sentence Source: `massif`"
+        ]
+    );
+}
+
+#[test]
+fn uses_the_default_sources_without_a_config() {
+    let (registry, errors) = SourceRegistry::from_config(&ConfigJson::default());
+
+    assert_eq!(registry.names(), DEFAULT_SOURCES.to_vec());
+    assert!(errors.is_empty());
+}
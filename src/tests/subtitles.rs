@@ -0,0 +1,87 @@
+use crate::app::Expression;
+use crate::sources::subtitles::*;
+use crate::sources::SentenceSource;
+use std::time::Duration;
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn parses_srt_and_ass_timestamps() {
+    assert_eq!(parse_timestamp("00:01:02,345"), Some(ms(62_345)));
+    assert_eq!(parse_timestamp(" 1:00:00.50 "), Some(ms(3_600_500)));
+    assert_eq!(parse_timestamp("0:00:07.5"), Some(ms(7_500)));
+    assert_eq!(parse_timestamp("00:01"), None);
+    assert_eq!(parse_timestamp("aa:00:00,000"), None);
+}
+
+#[test]
+fn parses_srt_blocks_without_tags() {
+    let srt = "1\n00:00:01,000 --> 00:00:02,500\n<i>パンを</i>\n食べた。\n\n\
+        2\n00:00:03,000 --> 00:00:04,000\n{\\an8}\n\n\
+        3\nbroken --> 00:00:05,000\n寝た。\n\n\
+        4\n00:00:06,000 --> 00:00:07,000\n起きた。";
+
+    assert_eq!(
+        parse_srt(srt),
+        vec![
+            (ms(1_000), ms(2_500), "パンを食べた。".to_string()),
+            (ms(6_000), ms(7_000), "起きた。".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn parses_ass_dialogue_lines() {
+    let ass = "[Events]\n\
+        Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+        Comment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,メモ\n\
+        Dialogue: 0,0:00:01.20,0:00:03.45,Default,,0,0,0,,{\\i1}パンを{\\i0}\\N食べた、よ。\n\
+        Dialogue: 0,0:00:04.00,0:00:05.00,Default,,0,0,0,,{\\pos(1,1)}\n\
+        Dialogue: 0,0:00:06.00,0:00:07.00,Default\n";
+
+    assert_eq!(
+        parse_ass(ass),
+        vec![(ms(1_200), ms(3_450), "パンを食べた、よ。".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn attaches_clips_without_cutting_them_and_picks_up_new_files() {
+    let dir = std::env::temp_dir().join(format!("anki_helper_subtitles_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("show")).unwrap();
+    std::fs::write(
+        dir.join("show/ep01.ja.srt"),
+        "1\n00:00:01,000 --> 00:00:02,000\nパンを食べた。\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("show/ep01.mkv"), b"").unwrap();
+    let source = SubtitleSource::new(&SubtitlesConfig {
+        dir: dir.to_string_lossy().to_string(),
+        ffmpeg: "does-not-exist".to_string(),
+    });
+    let exp = Expression::from("食べる".to_string(), None, None, None);
+
+    let res = source.fetch(&exp).await.unwrap();
+
+    let first = &res.sentences[0];
+    assert_eq!(first.media_title, "ep01.ja");
+    assert_eq!(first.timestamps, Some((ms(1_000), ms(2_000))));
+    assert_eq!(first.audio_data, None);
+    let clip = first.clip.as_ref().unwrap();
+    assert_eq!(clip.media, dir.join("show/ep01.mkv"));
+    assert_eq!((clip.start, clip.end), (ms(1_000), ms(2_000)));
+
+    std::fs::write(
+        dir.join("ep02.srt"),
+        "1\n00:00:03,000 --> 00:00:04,000\nご飯を食べます。\n",
+    )
+    .unwrap();
+
+    let res = source.fetch(&exp).await.unwrap();
+    assert_eq!(res.sentences.len(), 2);
+    assert_eq!(res.sentences[0].clip, None);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,26 @@
+use crate::tasks::*;
+use std::time::Duration;
+
+fn failed_load(err: &str) -> TaskResult {
+    TaskResult::Tokenizer {
+        result: Err(err.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn drops_the_results_of_cancelled_tasks() {
+    let mut tasks = TaskQueue::default();
+    tasks.spawn(TaskKind::Tokenizer, None, async { failed_load("old") });
+    // finished & sent, but not received yet
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(tasks.cancel(TaskKind::Tokenizer, None), 1);
+    tasks.spawn(TaskKind::Tokenizer, None, async { failed_load("new") });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let Some(TaskResult::Tokenizer { result: Err(err) }) = tasks.try_recv() else {
+        panic!("expected the newer task's result");
+    };
+    assert_eq!(err, "new");
+    assert!(tasks.try_recv().is_none());
+    assert!(!tasks.is_running(TaskKind::Tokenizer));
+}
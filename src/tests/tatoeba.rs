@@ -0,0 +1,116 @@
+use crate::app::Expression;
+use crate::sources::tatoeba::*;
+use crate::sources::SentenceSource;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+const SENTENCES: &str = "1\tjpn\tパンを食べた。\n\
+    2\teng\tI ate bread.\n\
+    3\tjpn\t毎朝ご飯を食べます。\n\
+    4\teng\tI eat rice every morning.\n\
+    5\tfra\tJ'ai mangé du pain.\n\
+    6\tjpn\t水を飲む。\n\
+    7\tjpn\t食べる。\n\
+    not a row\n";
+const LINKS: &str = "1\t2\n1\t5\n2\t1\n3\t4\n6\t99\n";
+
+/// The csv dumps in a temp dir, with the index next to them.
+fn fixture(name: &str) -> (TatoebaConfig, PathBuf) {
+    let dir = std::env::temp_dir().join(format!(
+        "anki_helper_tatoeba_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("sentences.csv"), SENTENCES).unwrap();
+    std::fs::write(dir.join("links.csv"), LINKS).unwrap();
+    let path = |file: &str| dir.join(file).to_string_lossy().to_string();
+    let config = TatoebaConfig {
+        sentences_csv: path("sentences.csv"),
+        links_csv: path("links.csv"),
+        index_path: path("index.json"),
+    };
+    (config, dir)
+}
+
+#[test]
+fn joins_japanese_sentences_with_their_english_translations() {
+    let (config, dir) = fixture("build");
+
+    let entries = build_index(&config.sentences_csv, &config.links_csv).unwrap();
+
+    let entries: Vec<(u64, &str, Vec<&str>)> = entries
+        .iter()
+        .map(|e| {
+            let translations = e.translations.iter().map(String::as_str).collect();
+            (e.id, e.text.as_str(), translations)
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (7, "食べる。", vec![]),
+            (6, "水を飲む。", vec![]),
+            (1, "パンを食べた。", vec!["I ate bread."]),
+            (3, "毎朝ご飯を食べます。", vec!["I eat rice every morning."]),
+        ]
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rebuilds_the_index_when_the_csvs_are_newer() {
+    let (config, dir) = fixture("rebuild");
+    assert_eq!(load_or_build_index(&config).unwrap().len(), 4);
+
+    std::fs::write(&config.sentences_csv, "8\tjpn\t寝る。\n").unwrap();
+    let index = std::fs::File::options()
+        .write(true)
+        .open(&config.index_path)
+        .unwrap();
+    index
+        .set_modified(SystemTime::now() - Duration::from_secs(60))
+        .unwrap();
+
+    let entries = load_or_build_index(&config).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].text, "寝る。");
+
+    // the index is used as is once the csvs are gone
+    std::fs::remove_file(&config.sentences_csv).unwrap();
+    assert_eq!(load_or_build_index(&config).unwrap().len(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn finds_inflected_sentences_with_their_translation() {
+    let (config, dir) = fixture("search");
+    let source = TatoebaSource::new(&config);
+    let exp = Expression::from("食べる".to_string(), None, None, None);
+
+    let res = source.fetch(&exp).await.unwrap();
+
+    let found: Vec<_> = res
+        .sentences
+        .iter()
+        .map(|s| (s.sentence.as_str(), s.translation.as_deref(), s.highlight))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("食べる。", None, Some((0, 9))),
+            ("パンを食べた。", Some("I ate bread."), Some((9, 18))),
+            (
+                "毎朝ご飯を食べます。",
+                Some("I eat rice every morning."),
+                Some((15, 27))
+            ),
+        ]
+    );
+    assert_eq!(
+        res.sentences[1].wbst_link,
+        "https://tatoeba.org/en/sentences/show/1"
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,41 @@
+use crate::app::Expression;
+use crate::yomitan::*;
+
+fn term(expression: &str, reading: &str, gloss: &str) -> Term {
+    Term {
+        expression: expression.to_string(),
+        reading: reading.to_string(),
+        tags: String::new(),
+        glossary: vec![gloss.to_string()],
+    }
+}
+
+fn store() -> YomitanStore {
+    YomitanStore::new(vec![YomitanDict {
+        title: "JMdict".to_string(),
+        terms: vec![
+            term("橋", "はし", "bridge"),
+            term("箸", "はし", "chopsticks"),
+            term("はし", "はし", "edge (kana)"),
+            term("端", "はた", "side"),
+        ],
+        ..Default::default()
+    }])
+}
+
+fn definitions(word: &str) -> Vec<String> {
+    let mut exp = Expression::from(word.to_string(), None, None, None);
+    exp.apply_yomitan(&store());
+    exp.definitions
+}
+
+#[test]
+fn leaves_out_homophones_of_kana_words() {
+    assert_eq!(definitions("はし"), vec!["edge (kana)"]);
+    assert_eq!(definitions("橋"), vec!["bridge"]);
+}
+
+#[test]
+fn falls_back_to_reading_matches() {
+    assert_eq!(definitions("はた"), vec!["side"]);
+}
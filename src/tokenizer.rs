@@ -0,0 +1,204 @@
+use crate::app::*;
+use crate::deinflect::is_inflection_of;
+use crate::tasks::{TaskKind, TaskResult};
+use lindera::dictionary::{load_dictionary, load_fs_dictionary};
+use lindera::mode::Mode;
+use lindera::segmenter::Segmenter;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Most tokens an inflected word is split into, ie. 食べ・られ・なかっ・た.
+const MAX_WORD_TOKENS: usize = 6;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TokenizerConfig {
+    /// A compiled Lindera dictionary directory to use instead of the embedded IPADIC.
+    pub dictionary: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub surface: String,
+    /// The dictionary form, ie. 食べる for 食べ.
+    pub base_form: String,
+    /// Katakana, empty for unknown words.
+    pub reading: String,
+    pub pos: String,
+    /// Byte range in the sentence.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token {
+    /// Particles, auxiliaries & punctuation, which aren't words of their own.
+    pub fn is_function_word(&self) -> bool {
+        matches!(self.pos.as_str(), "助詞" | "助動詞" | "記号")
+    }
+}
+
+pub struct Tokenizer {
+    segmenter: Segmenter,
+}
+
+impl Tokenizer {
+    /// Loads the dictionary at `path`, or the embedded IPADIC.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let dictionary = match path {
+            Some(path) if !path.is_dir() => {
+                return Err(format!("Dictionary `{}` not found", path.display()))
+            }
+            Some(path) => load_fs_dictionary(path),
+            None => load_dictionary("embedded://ipadic"),
+        };
+        let dictionary = dictionary.map_err(|err| err.to_string())?;
+        Ok(Self {
+            segmenter: Segmenter::new(Mode::Normal, dictionary, None),
+        })
+    }
+
+    pub fn tokenize(&self, text: &str) -> Vec<Token> {
+        let Ok(tokens) = self.segmenter.segment(Cow::Borrowed(text)) else {
+            return Vec::new();
+        };
+
+        tokens
+            .into_iter()
+            .map(|mut token| {
+                let surface = token.surface.to_string();
+                let (start, end) = (token.byte_start, token.byte_end);
+                // ipadic: pos, pos details ×3, conjugation type & form, base form, reading, pronunciation
+                let details = token.details();
+                let detail =
+                    |i: usize| details.get(i).filter(|d| **d != "*").map(|d| d.to_string());
+
+                Token {
+                    base_form: detail(6).unwrap_or_else(|| surface.clone()),
+                    reading: detail(7).unwrap_or_default(),
+                    pos: detail(0).unwrap_or_default(),
+                    surface,
+                    start,
+                    end,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Byte range of `word` in the sentence, spanning every token of its inflection.
+pub fn highlight(tokens: &[Token], sentence: &str, word: &str) -> Option<(usize, usize)> {
+    let word = word.trim();
+    for (i, token) in tokens.iter().enumerate() {
+        if !word.starts_with(token.surface.as_str()) && token.base_form != word {
+            continue;
+        }
+
+        // the longest run of tokens that's the word or one of its inflections
+        let last = (i..tokens.len().min(i + MAX_WORD_TOKENS))
+            .rev()
+            .find(|&j| is_inflection_of(&sentence[token.start..tokens[j].end], word));
+        match last {
+            Some(j) => return Some((token.start, tokens[j].end)),
+            None if token.base_form == word => return Some((token.start, token.end)),
+            None => {}
+        }
+    }
+    None
+}
+
+impl Sentence {
+    /// Splits the sentence into tokens, which also give its highlight, furigana & the words it's made of.
+    pub fn apply_tokenizer(&mut self, tokenizer: &Tokenizer) {
+        self.apply_tokens(tokenizer.tokenize(&self.sentence));
+    }
+
+    pub fn apply_tokens(&mut self, tokens: Vec<Token>) {
+        self.tokens = tokens;
+
+        let word = &self.parent_expression.dict_word;
+        if let Some(span) = highlight(&self.tokens, &self.sentence, word) {
+            self.highlight = Some(span);
+        }
+        self.furigana_from_tokens();
+        // Immersion Kit's word list is kept if there is one
+        if self.words.is_empty() {
+            self.words = self
+                .tokens
+                .iter()
+                .filter(|t| !t.is_function_word())
+                .map(|t| t.base_form.clone())
+                .collect();
+        }
+    }
+}
+
+impl AppState {
+    pub fn spawn_load_tokenizer(&mut self) {
+        let dictionary = &self.config.tokenizer.dictionary;
+        let path = (!dictionary.is_empty()).then(|| PathBuf::from(dictionary));
+
+        self.tasks.spawn(TaskKind::Tokenizer, None, async move {
+            let result = tokio::task::spawn_blocking(move || Tokenizer::load(path.as_deref()))
+                .await
+                .map_err(|err| err.to_string())
+                .and_then(|res| res.map(Box::new));
+            TaskResult::Tokenizer { result }
+        });
+    }
+
+    /// Tokenizes the sentences that were fetched before the tokenizer was loaded, in a task.
+    pub fn set_tokenizer(&mut self, tokenizer: Box<Tokenizer>) {
+        let tokenizer: Arc<Tokenizer> = Arc::from(tokenizer);
+        self.tokenizer = Some(tokenizer.clone());
+
+        let sentences: Vec<(String, String)> = self
+            .expressions
+            .iter()
+            .flat_map(|exp| {
+                let untokenized = exp
+                    .unfiltered_sentences
+                    .iter()
+                    .filter(|s| s.tokens.is_empty());
+                untokenized.map(|s| (exp.dict_word.clone(), s.sentence.clone()))
+            })
+            .collect();
+        if sentences.is_empty() {
+            return;
+        }
+
+        self.tasks.spawn(TaskKind::Tokenizer, None, async move {
+            let tokens = tokio::task::spawn_blocking(move || {
+                sentences
+                    .into_iter()
+                    .map(|(dict_word, sentence)| {
+                        let tokens = tokenizer.tokenize(&sentence);
+                        ((dict_word, sentence), tokens)
+                    })
+                    .collect()
+            })
+            .await
+            .unwrap_or_default();
+            TaskResult::Tokens { tokens }
+        });
+    }
+
+    /// Applies the tokens of sentences that weren't tokenized yet, by expression & text.
+    pub fn set_sentence_tokens(&mut self, tokens: HashMap<(String, String), Vec<Token>>) {
+        for exp in self.expressions.iter_mut() {
+            let dict_word = exp.dict_word.clone();
+            for sentence in exp.unfiltered_sentences.iter_mut() {
+                if !sentence.tokens.is_empty() {
+                    continue;
+                }
+                let key = (dict_word.clone(), sentence.sentence.clone());
+                if let Some(tokens) = tokens.get(&key) {
+                    sentence.apply_tokens(tokens.clone());
+                }
+            }
+        }
+        self.refilter_sentences();
+    }
+}
@@ -1,7 +1,9 @@
-use crate::app::{AppState, Pages, SelectMode, Sentence};
+use crate::app::{AppState, Expression, Pages, SelectMode, Sentence};
+use crate::furigana::{parse_anki, ruby_lines};
+use crate::pitch::{overline_notation, unique_pitches};
 use ratatui::{
     prelude::*,
-    widgets::{Block, List, ListItem, Paragraph},
+    widgets::{Block, List, ListItem, Paragraph, Wrap},
 };
 use rayon::prelude::*;
 
@@ -23,6 +25,18 @@ impl Widget for &mut AppState {
             Pages::Help => {
                 self.rend_help_page(area, buf);
             }
+            Pages::Filters => {
+                self.rend_filter_picker(area, buf);
+            }
+            Pages::Results => {
+                self.rend_update_results(area, buf);
+            }
+            Pages::Preview => {
+                self.rend_update_preview(area, buf);
+            }
+            Pages::History => {
+                self.rend_update_history(area, buf);
+            }
             Pages::Splice => {
                 //self.rend_splice_page(area, buf);
             }
@@ -101,10 +115,15 @@ impl AppState {
 
     fn rend_media_title(&mut self, area: Rect, buf: &mut Buffer) {
         if let Some(sentence) = &self.get_current_sentence() {
-            let (msg, style) = (
-                vec![sentence.media_title.clone().into()],
-                Style::default().yellow(),
-            );
+            let mut msg = vec![sentence.media_title.clone().into()];
+            if let Some((start, _end)) = sentence.timestamps {
+                let secs = start.as_secs();
+                msg.push(
+                    format!(" [{:02}:{:02}:{:02}]", secs / 3600, secs / 60 % 60, secs % 60)
+                        .white(),
+                );
+            }
+            let style = Style::default().yellow();
             let text = Text::from(Line::from(msg).patch_style(style));
             Paragraph::new(text)
                 .block(
@@ -151,15 +170,153 @@ impl AppState {
         }
     }
 
+    fn rend_translation(&mut self, area: Rect, buf: &mut Buffer) {
+        if let Some(translation) = self
+            .get_current_sentence()
+            .and_then(|sentence| sentence.translation)
+        {
+            Paragraph::new(Text::from(translation).style(Color::White))
+                .block(
+                    Block::bordered().title(Line::styled("Translation", Style::default().yellow())),
+                )
+                .style(Color::Green)
+                .wrap(Wrap { trim: true })
+                .render(area, buf);
+        }
+    }
+
+    fn sentence_details(sentence: &Sentence, width: usize) -> Vec<Line<'static>> {
+        let mut details = Vec::new();
+        if let Some(furigana) = &sentence.furigana {
+            for (readings, text) in ruby_lines(&parse_anki(furigana), width) {
+                details.push(Line::styled(readings, Style::default().yellow()));
+                details.push(Line::styled(text, Style::default().white()));
+            }
+        }
+        let mut push = |title: &str, value: String| {
+            details.push(Line::from(vec![
+                Span::styled(format!("{}: ", title), Style::default().yellow()),
+                Span::styled(value, Style::default().white()),
+            ]));
+        };
+
+        if let Some(episode) = &sentence.episode {
+            push("Episode", episode.clone());
+        }
+        if let Some(category) = &sentence.category {
+            push("Category", category.clone());
+        }
+        if !sentence.tags.is_empty() {
+            push("Tags", sentence.tags.join(", "));
+        }
+        let matched = sentence.matched_words();
+        if !matched.is_empty() {
+            push("Matched", matched.join("・"));
+        }
+        if !sentence.tokens.is_empty() {
+            let tokens: Vec<String> = sentence
+                .tokens
+                .iter()
+                .map(|t| {
+                    if t.base_form == t.surface {
+                        t.surface.clone()
+                    } else {
+                        format!("{}({})", t.surface, t.base_form)
+                    }
+                })
+                .collect();
+            push("Tokens", tokens.join(" "));
+        }
+        details
+    }
+
+    fn rend_sentence_details(&mut self, area: Rect, details: Vec<Line<'static>>, buf: &mut Buffer) {
+        if details.is_empty() {
+            return;
+        }
+        Paragraph::new(details)
+            .block(Block::bordered().title(Line::styled("Details", Style::default().yellow())))
+            .style(Color::Green)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+
+    fn dictionary_info(exp: &Expression) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        if !exp.frequencies.is_empty() {
+            let mut spans = vec![Span::styled("Freq: ", Style::default().yellow())];
+            for (i, freq) in exp.frequencies.iter().enumerate() {
+                if i > 0 {
+                    spans.push(Span::styled(" | ", Color::Green));
+                }
+                spans.push(Span::styled(format!("{} ", freq.dict), Color::White));
+                spans.push(Span::styled(freq.display.clone(), Style::default().yellow()));
+            }
+            lines.push(Line::from(spans));
+        }
+        for (reading, position) in unique_pitches(&exp.pitches) {
+            let (overline, text) = overline_notation(reading, position);
+            lines.push(Line::from(vec![
+                Span::raw("       "),
+                Span::styled(overline, Style::default().yellow()),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled("Pitch: ", Style::default().yellow()),
+                Span::styled(text, Color::White),
+                Span::styled(format!(" [{}]", position), Style::default().dim()),
+            ]));
+        }
+        lines
+    }
+
+    fn rend_dictionary_info(&mut self, area: Rect, buf: &mut Buffer) {
+        if let Some(i) = self.selected_expression {
+            let lines = Self::dictionary_info(&self.expressions[i]);
+            if lines.is_empty() {
+                return;
+            }
+            // not wrapped, so the overlines stay above their morae
+            Paragraph::new(lines)
+                .block(Block::bordered().title(Line::styled("Dictionary", Style::default().yellow())))
+                .style(Color::Green)
+                .render(area, buf);
+        }
+    }
+
     fn rend_sentence_info(&mut self, area: Rect, buf: &mut Buffer) {
         if let Some(i) = self.selected_expression {
+            let sentence = self.get_current_sentence();
+            let translation_len = sentence
+                .as_ref()
+                .and_then(|sentence| sentence.translation.as_ref())
+                .map_or(0, |t| (t.chars().count() as u16 / area.width.max(1)) + 3);
+            // built once per frame, the furigana is parsed & laid out for the given width
+            let sentence_details = sentence.as_ref().map_or(Vec::new(), |sentence| {
+                Self::sentence_details(sentence, area.width.saturating_sub(2) as usize)
+            });
+            let details_len = match sentence_details.len() as u16 {
+                0 => 0,
+                len => len + 2,
+            };
+
+            let dictionary_len = match Self::dictionary_info(&self.expressions[i]).len() as u16 {
+                0 => 0,
+                len => len + 2,
+            };
+
             let vertical = Layout::vertical([
                 Constraint::Length(3),
+                Constraint::Length(translation_len),
+                Constraint::Length(details_len),
+                Constraint::Length(dictionary_len),
                 Constraint::Length((self.expressions[i].definitions.len() + 2) as u16),
             ]);
-            let [top, top_middle] = vertical.areas(area);
+            let [top, top_middle, details, dictionary, middle] = vertical.areas(area);
             self.rend_media_title(top, buf);
-            self.rend_sentence_defs(top_middle, buf)
+            self.rend_translation(top_middle, buf);
+            self.rend_sentence_details(details, sentence_details, buf);
+            self.rend_dictionary_info(dictionary, buf);
+            self.rend_sentence_defs(middle, buf)
         }
     }
 
@@ -192,12 +349,18 @@ impl AppState {
     }
 
     fn rend_expressions(&mut self, area: Rect, buf: &mut Buffer) {
-        let words: Vec<ListItem> = self
-            .expressions
+        let loading = self.tasks.loading_words();
+        let spinner = self.tasks.spinner();
+        // expressions hidden by their frequency rank are left out, so the list's selection is
+        // their position among the visible ones
+        let visible = self.visible_expressions();
+        let words: Vec<ListItem> = visible
             .par_iter()
-            .enumerate()
-            .map(|(i, exp)| {
-                let mut item = exp.to_list_item(i);
+            .map(|&i| {
+                let exp = &self.expressions[i];
+                let is_loading = loading.contains(&exp.dict_word);
+                let mut item =
+                    exp.to_list_item(i, is_loading.then_some(spinner), &self.config.frequency);
                 if self
                     .notes_to_be_created
                     .sentences
@@ -235,7 +398,14 @@ impl AppState {
         //.highlight_symbol("⇢ ");
         //.highlight_spacing(ratatui::widgets::HighlightSpacing::Always);
 
-        StatefulWidget::render(words, area, buf, &mut self.expressions_state);
+        let mut state = self.expressions_state.clone();
+        state.select(
+            self.expressions_state
+                .selected()
+                .and_then(|i| visible.iter().position(|&v| v == i)),
+        );
+        StatefulWidget::render(words, area, buf, &mut state);
+        *self.expressions_state.offset_mut() = state.offset();
     }
 
     fn rend_sentences(&mut self, sentences_area: Rect, info_area: Rect, buf: &mut Buffer) {
@@ -264,8 +434,12 @@ impl AppState {
                     .enumerate()
                     .map(|(i, sentence)| {
                         let sent_obj = &sentence;
-                        let item =
-                            AppState::sentence_to_list_item(&sent_obj.sentence, &dict_word, i);
+                        let item = AppState::sentence_to_list_item(
+                            &sent_obj.sentence,
+                            sent_obj.highlight,
+                            i,
+                            sent_obj.unknown_words,
+                        );
                         if self.notes_to_be_created.sentences.contains(sent_obj) {
                             return item.bg(Color::Green);
                         }
@@ -512,25 +686,34 @@ impl AppState {
             .render(area, buf);
     }
 
-    pub fn sentence_to_list_item<'a>(sentence: &'a str, word: &'a str, i: usize) -> ListItem<'a> {
-        let (start, end) = sentence
-            .match_indices(word)
-            .next()
-            .map(|(start, word)| (start, start + word.len()))
-            .unwrap_or((0, 0));
+    pub fn sentence_to_list_item<'a>(
+        sentence: &'a str,
+        highlight: Option<(usize, usize)>,
+        i: usize,
+        unknown_words: Option<usize>,
+    ) -> ListItem<'a> {
+        let (start, end) = highlight.unwrap_or((0, 0));
 
         let before_word = &sentence[..start];
         let found_word = &sentence[start..end];
         let after_word = &sentence[end..];
 
-        let mixed_line = Line::from(vec![
+        let mut mixed_line = Line::from(vec![
             //Span::styled("|", Color::Green),
             Span::styled(i.to_string(), Style::default().yellow()),
             Span::styled(". ", Color::Green),
-            Span::styled(before_word, Color::White),
-            Span::styled(found_word, Style::default().yellow()),
-            Span::styled(after_word, Color::White),
         ]);
+        if let Some(unknown) = unknown_words {
+            let color = match unknown {
+                0 | 1 => Color::Green,
+                2 | 3 => Color::Yellow,
+                _ => Color::Red,
+            };
+            mixed_line.push_span(Span::styled(format!("[i+{}] ", unknown), color));
+        }
+        mixed_line.push_span(Span::styled(before_word, Color::White));
+        mixed_line.push_span(Span::styled(found_word, Style::default().yellow()));
+        mixed_line.push_span(Span::styled(after_word, Color::White));
 
         ListItem::new(mixed_line)
     }
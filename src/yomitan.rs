@@ -0,0 +1,426 @@
+use crate::app::*;
+use crate::pitch::read_kanjium;
+use crate::tasks::{TaskKind, TaskResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub type YomitanError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct YomitanConfig {
+    /// Yomitan dictionary zips, in order of priority.
+    pub dictionaries: Vec<String>,
+    /// Where the imported dictionaries are stored.
+    pub index_dir: String,
+}
+
+impl Default for YomitanConfig {
+    fn default() -> Self {
+        Self {
+            dictionaries: Vec::new(),
+            index_dir: "data/yomitan".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Term {
+    pub expression: String,
+    pub reading: String,
+    pub tags: String,
+    pub glossary: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Frequency {
+    pub dict: String,
+    pub expression: String,
+    pub reading: Option<String>,
+    /// Rank, lower is more frequent.
+    pub value: u64,
+    pub display: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Pitch {
+    pub dict: String,
+    pub expression: String,
+    pub reading: String,
+    /// Downstep positions, 0 is heiban.
+    pub positions: Vec<u8>,
+}
+
+/// An imported dictionary, stored as json in `index_dir`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct YomitanDict {
+    pub title: String,
+    pub terms: Vec<Term>,
+    pub frequencies: Vec<Frequency>,
+    pub pitches: Vec<Pitch>,
+}
+
+/// All imported dictionaries, looked up by expression or reading.
+#[derive(Debug, Default)]
+pub struct YomitanStore {
+    dicts: Vec<YomitanDict>,
+    terms: HashMap<String, Vec<(usize, usize)>>,
+    frequencies: HashMap<String, Vec<(usize, usize)>>,
+    pitches: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl YomitanStore {
+    pub fn new(dicts: Vec<YomitanDict>) -> Self {
+        let mut store = Self::default();
+        for (d, dict) in dicts.iter().enumerate() {
+            for (i, term) in dict.terms.iter().enumerate() {
+                index(&mut store.terms, &term.expression, (d, i));
+                index(&mut store.terms, &term.reading, (d, i));
+            }
+            for (i, freq) in dict.frequencies.iter().enumerate() {
+                index(&mut store.frequencies, &freq.expression, (d, i));
+            }
+            for (i, pitch) in dict.pitches.iter().enumerate() {
+                index(&mut store.pitches, &pitch.expression, (d, i));
+            }
+        }
+        store.dicts = dicts;
+        store
+    }
+
+    pub fn titles(&self) -> Vec<&str> {
+        self.dicts.iter().map(|d| d.title.as_str()).collect()
+    }
+
+    pub fn terms(&self, word: &str) -> Vec<&Term> {
+        lookup(&self.terms, word)
+            .map(|(d, i)| &self.dicts[d].terms[i])
+            .collect()
+    }
+
+    pub fn frequencies(&self, word: &str) -> Vec<&Frequency> {
+        lookup(&self.frequencies, word)
+            .map(|(d, i)| &self.dicts[d].frequencies[i])
+            .collect()
+    }
+
+    pub fn pitches(&self, word: &str) -> Vec<&Pitch> {
+        lookup(&self.pitches, word)
+            .map(|(d, i)| &self.dicts[d].pitches[i])
+            .collect()
+    }
+}
+
+fn index(map: &mut HashMap<String, Vec<(usize, usize)>>, key: &str, id: (usize, usize)) {
+    if key.is_empty() {
+        return;
+    }
+    let ids = map.entry(key.to_string()).or_default();
+    if !ids.contains(&id) {
+        ids.push(id);
+    }
+}
+
+fn lookup<'a>(
+    map: &'a HashMap<String, Vec<(usize, usize)>>,
+    word: &str,
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    map.get(word.trim()).into_iter().flatten().copied()
+}
+
+impl Term {
+    /// `(tags) gloss; gloss`
+    pub fn to_definition(&self) -> String {
+        if self.tags.is_empty() {
+            return self.glossary.join("; ");
+        }
+        format!("({}) {}", self.tags, self.glossary.join("; "))
+    }
+}
+
+impl Expression {
+    /// Fills in frequencies, pitch accents, readings & definitions (if there are none yet).
+    pub fn apply_yomitan(&mut self, store: &YomitanStore) {
+        let mut terms = store.terms(&self.dict_word);
+        // a kana word also finds its homophones by their reading, they're only kept if it's
+        // not an expression itself
+        if terms.iter().any(|t| t.expression == self.dict_word) {
+            terms.retain(|t| t.expression == self.dict_word);
+        }
+        for term in terms.iter().filter(|t| t.expression == self.dict_word) {
+            if !term.reading.is_empty()
+                && term.reading != self.dict_word
+                && !self.readings.contains(&term.reading)
+            {
+                self.readings.push(term.reading.clone());
+            }
+        }
+        if self.definitions.is_empty() {
+            self.definitions = terms.iter().map(|t| t.to_definition()).collect();
+        }
+
+        self.frequencies = store
+            .frequencies(&self.dict_word)
+            .into_iter()
+            .cloned()
+            .collect();
+        self.pitches = store
+            .pitches(&self.dict_word)
+            .into_iter()
+            .cloned()
+            .collect();
+    }
+}
+
+impl AppState {
+    pub fn spawn_load_yomitan(&mut self) {
+        let kanjium = PathBuf::from(&self.config.pitch.kanjium);
+        if self.config.yomitan.dictionaries.is_empty() && !kanjium.exists() {
+            return;
+        }
+        let config = self.config.yomitan.clone();
+
+        self.tasks.spawn(TaskKind::Yomitan, None, async move {
+            let result = tokio::task::spawn_blocking(move || load_dictionaries(&config, &kanjium))
+                .await
+                .map_err(|err| err.to_string());
+            TaskResult::Yomitan { result }
+        });
+    }
+
+    pub fn set_yomitan(&mut self, store: YomitanStore, errors: Vec<String>) {
+        for err in errors {
+            self.update_error_msg("Error Importing Yomitan Dictionary", err);
+        }
+        for exp in self.expressions.iter_mut() {
+            exp.apply_yomitan(&store);
+        }
+        self.info.msg = Some(format!("Loaded Yomitan: {}", store.titles().join(", ")));
+        self.yomitan = Some(Arc::new(store));
+        self.apply_frequency_options(self.config.frequency.sort);
+    }
+}
+
+/// Loads every dictionary in `config.dictionaries`, importing the ones that changed since the last import,
+/// followed by Kanjium's pitch accents if the file exists.
+/// Dictionaries that fail to import are skipped & returned as errors.
+fn load_dictionaries(config: &YomitanConfig, kanjium: &Path) -> (YomitanStore, Vec<String>) {
+    let mut dicts = Vec::new();
+    let mut errors = Vec::new();
+
+    for zip_path in &config.dictionaries {
+        match load_or_import(Path::new(zip_path), Path::new(&config.index_dir)) {
+            Ok(dict) => dicts.push(dict),
+            Err(err) => errors.push(format!("{}: {}", zip_path, err)),
+        }
+    }
+
+    if kanjium.exists() {
+        match read_kanjium(kanjium) {
+            Ok(pitches) => dicts.push(YomitanDict {
+                title: "Kanjium".to_string(),
+                pitches,
+                ..YomitanDict::default()
+            }),
+            Err(err) => errors.push(format!("{}: {}", kanjium.display(), err)),
+        }
+    }
+
+    (YomitanStore::new(dicts), errors)
+}
+
+fn load_or_import(zip_path: &Path, index_dir: &Path) -> Result<YomitanDict, YomitanError> {
+    let stem = zip_path
+        .file_stem()
+        .ok_or("invalid dictionary path")?
+        .to_string_lossy();
+    let index_path: PathBuf = index_dir.join(format!("{}.json", stem));
+
+    let is_fresh = match (index_path.metadata(), zip_path.metadata()) {
+        (Ok(index), Ok(zip)) => index.modified()? >= zip.modified()?,
+        (Ok(_), Err(_)) => true,
+        _ => false,
+    };
+    if is_fresh {
+        let reader = BufReader::new(File::open(&index_path)?);
+        return Ok(serde_json::from_reader(reader)?);
+    }
+
+    let dict = import_zip(zip_path)?;
+    std::fs::create_dir_all(index_dir)?;
+    serde_json::to_writer(BufWriter::new(File::create(&index_path)?), &dict)?;
+    Ok(dict)
+}
+
+/// Reads `index.json`, `term_bank_*.json` & `term_meta_bank_*.json` from a Yomitan dictionary zip.
+pub fn import_zip(path: &Path) -> Result<YomitanDict, YomitanError> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut dict = YomitanDict::default();
+
+    let names: Vec<String> = archive.file_names().map(|n| n.to_string()).collect();
+    for name in &names {
+        let is_term_bank = name.starts_with("term_bank_");
+        let is_meta_bank = name.starts_with("term_meta_bank_");
+        if name != "index.json" && !is_term_bank && !is_meta_bank {
+            continue;
+        }
+
+        let mut json = String::new();
+        archive.by_name(name)?.read_to_string(&mut json)?;
+        let value: Value = serde_json::from_str(&json)?;
+
+        if name == "index.json" {
+            dict.title = value["title"].as_str().unwrap_or_default().to_string();
+            continue;
+        }
+
+        for row in value.as_array().ok_or("bank isn't an array")? {
+            if is_term_bank {
+                dict.terms.extend(parse_term(row));
+            } else {
+                parse_meta(row, &mut dict);
+            }
+        }
+    }
+
+    if dict.title.is_empty() {
+        dict.title = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+    }
+    let title = dict.title.clone();
+    for freq in dict.frequencies.iter_mut() {
+        freq.dict.clone_from(&title);
+    }
+    for pitch in dict.pitches.iter_mut() {
+        pitch.dict.clone_from(&title);
+    }
+
+    Ok(dict)
+}
+
+/// `[expression, reading, definitionTags, rules, score, glossary, sequence, termTags]`
+fn parse_term(row: &Value) -> Option<Term> {
+    let row = row.as_array()?;
+    let mut glossary = Vec::new();
+    for gloss in row.get(5)?.as_array()? {
+        let text = glossary_text(gloss);
+        if !text.trim().is_empty() {
+            glossary.push(text.trim().to_string());
+        }
+    }
+
+    Some(Term {
+        expression: row.first()?.as_str()?.to_string(),
+        reading: row.get(1)?.as_str().unwrap_or_default().to_string(),
+        tags: row.get(2)?.as_str().unwrap_or_default().to_string(),
+        glossary,
+    })
+}
+
+/// Flattens structured content glossaries into plain text.
+fn glossary_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(glossary_text).collect(),
+        Value::Object(obj) => match obj.get("type").and_then(|t| t.as_str()) {
+            Some("image") => String::new(),
+            Some("text") => obj.get("text").map(glossary_text).unwrap_or_default(),
+            _ => {
+                let text = obj.get("content").map(glossary_text).unwrap_or_default();
+                match obj.get("tag").and_then(|t| t.as_str()) {
+                    Some("li" | "div" | "p") if !text.is_empty() => format!("{} ", text),
+                    Some("rt" | "rp") => String::new(),
+                    _ => text,
+                }
+            }
+        },
+        _ => String::new(),
+    }
+}
+
+/// `[expression, "freq" | "pitch", data]`
+fn parse_meta(row: &Value, dict: &mut YomitanDict) {
+    let Some(row) = row.as_array() else {
+        return;
+    };
+    let (Some(expression), Some(mode), Some(data)) = (
+        row.first().and_then(|v| v.as_str()),
+        row.get(1).and_then(|v| v.as_str()),
+        row.get(2),
+    ) else {
+        return;
+    };
+
+    match mode {
+        "freq" => {
+            let (reading, data) = match data.get("reading").and_then(|r| r.as_str()) {
+                Some(reading) => (Some(reading.to_string()), &data["frequency"]),
+                None => (None, data),
+            };
+            if let Some((value, display)) = parse_freq_value(data) {
+                dict.frequencies.push(Frequency {
+                    dict: String::new(),
+                    expression: expression.to_string(),
+                    reading,
+                    value,
+                    display,
+                });
+            }
+        }
+        "pitch" => {
+            let Some(reading) = data.get("reading").and_then(|r| r.as_str()) else {
+                return;
+            };
+            let positions = data["pitches"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|p| p.get("position")?.as_u64())
+                .map(|p| p as u8)
+                .collect();
+            dict.pitches.push(Pitch {
+                dict: String::new(),
+                expression: expression.to_string(),
+                reading: reading.to_string(),
+                positions,
+            });
+        }
+        _ => {}
+    }
+}
+
+/// A frequency is a number, a string, or `{ value, displayValue }`.
+fn parse_freq_value(data: &Value) -> Option<(u64, String)> {
+    match data {
+        Value::Number(n) => {
+            let value = n.as_f64()? as u64;
+            Some((value, value.to_string()))
+        }
+        Value::String(s) => {
+            let value = s
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect::<String>()
+                .parse()
+                .ok()?;
+            Some((value, s.clone()))
+        }
+        Value::Object(obj) => {
+            let value = obj.get("value")?.as_f64()? as u64;
+            let display = obj
+                .get("displayValue")
+                .and_then(|d| d.as_str())
+                .map(|d| d.to_string())
+                .unwrap_or_else(|| value.to_string());
+            Some((value, display))
+        }
+        _ => None,
+    }
+}